@@ -0,0 +1,37 @@
+//! Client side account groups.
+//!
+//! These are the structs anchor generates from the zeta-cpi contexts, with
+//! pubkeys in place of `AccountInfo`s, so they follow the program as it
+//! changes. The `*Caller` contexts are built from their nested zeta context,
+//! e.g. `PlaceOrder` for `PlaceOrderCaller`, and `ZetaCpiClient` prepends the
+//! zeta program. Generated `to_account_metas` marks every `Signer` account as
+//! a signer whatever `is_signer` is passed. `MarketAccounts` and
+//! `CancelAccounts` are nested too deep to be re-exported by anchor and come
+//! from `zeta_context::client_accounts`.
+
+use anchor_lang::prelude::*;
+
+pub use zeta_cpi::accounts::*;
+pub use zeta_cpi::zeta_context::client_accounts::*;
+
+/// Accounts of one market traded by `place_orders`, passed as remaining
+/// accounts after `PlaceOrdersCaller` in the order of
+/// `PLACE_ORDERS_MARKET_ACCOUNTS`.
+pub struct PlaceOrdersMarketGroup {
+    pub open_orders: Pubkey,
+    pub market_accounts: MarketAccounts,
+    pub market_node: Pubkey,
+    pub market_mint: Pubkey,
+}
+
+impl ToAccountMetas for PlaceOrdersMarketGroup {
+    fn to_account_metas(&self, is_signer: Option<bool>) -> Vec<AccountMeta> {
+        let mut account_metas = vec![AccountMeta::new(self.open_orders, false)];
+        account_metas.extend(self.market_accounts.to_account_metas(is_signer));
+        account_metas.extend(vec![
+            AccountMeta::new(self.market_node, false),
+            AccountMeta::new(self.market_mint, false),
+        ]);
+        account_metas
+    }
+}
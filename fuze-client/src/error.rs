@@ -0,0 +1,23 @@
+use anchor_lang::prelude::Pubkey;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ClientError {
+    AccountNotFound(Pubkey),
+    InvalidAccountData(Pubkey),
+    Rpc(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::AccountNotFound(pubkey) => write!(f, "account {} not found", pubkey),
+            ClientError::InvalidAccountData(pubkey) => {
+                write!(f, "account {} has unexpected data", pubkey)
+            }
+            ClientError::Rpc(err) => write!(f, "rpc error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
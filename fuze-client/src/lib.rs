@@ -0,0 +1,97 @@
+//! Native Rust client for the zeta-cpi and vault programs.
+//!
+//! Instruction builders reuse the account layouts and argument types from the
+//! `zeta_cpi` crate. Chain state is read through the `RpcProvider` trait so
+//! bots can run against a live cluster and tests against an in-memory store.
+
+pub mod accounts;
+pub mod error;
+pub mod pda;
+pub mod rpc;
+pub mod vault_client;
+pub mod zeta_cpi_client;
+
+pub use crate::error::ClientError;
+pub use crate::rpc::*;
+pub use crate::vault_client::VaultClient;
+pub use crate::zeta_cpi_client::ZetaCpiClient;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::transaction::Transaction;
+
+/// Builds an unsigned transaction with a recent blockhash from `rpc`.
+pub fn build_transaction<R: RpcProvider>(
+    rpc: &R,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+) -> Result<Transaction, ClientError> {
+    let blockhash = rpc.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(instructions, Some(payer), &blockhash);
+    Ok(Transaction::new_unsigned(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::prelude::*;
+    use anchor_lang::InstructionData;
+    use bytemuck::Zeroable;
+    use zeta_cpi::instruction as ix;
+    use zeta_cpi::zeta_account::MarginAccount;
+
+    #[test]
+    fn test_deposit_round_trip() {
+        let zeta_program = Pubkey::new_unique();
+        let client = ZetaCpiClient::new(Pubkey::new_unique(), zeta_program);
+        let accounts = accounts::Deposit {
+            zeta_group: Pubkey::new_unique(),
+            margin_account: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            user_token_account: Pubkey::new_unique(),
+            socialized_loss_account: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            token_program: Pubkey::new_unique(),
+            state: Pubkey::new_unique(),
+            greeks: Pubkey::new_unique(),
+        };
+
+        let mut store = InMemoryAccountStore::new();
+        store.blockhash = anchor_lang::solana_program::hash::hash(b"blockhash");
+        let mut margin_account = MarginAccount::zeroed();
+        margin_account.balance = 1_000_000;
+        store.insert_zero_copy(accounts.margin_account, zeta_program, &margin_account);
+
+        let instruction = client.deposit(&accounts, 1_000_000);
+        let transaction = build_transaction(&store, &[instruction], &accounts.authority).unwrap();
+        let message = &transaction.message;
+        assert_eq!(message.recent_blockhash, store.blockhash);
+
+        // The zeta program is prepended to the generated zeta accounts, and
+        // only the authority signs.
+        let compiled = &message.instructions[0];
+        let keys: Vec<Pubkey> = compiled
+            .accounts
+            .iter()
+            .map(|&index| message.account_keys[index as usize])
+            .collect();
+        assert_eq!(keys[0], zeta_program);
+        assert_eq!(keys[2], accounts.margin_account);
+        assert_eq!(keys[6], accounts.authority);
+        assert_eq!(message.header.num_required_signatures, 1);
+        assert!(message.is_signer(compiled.accounts[6] as usize));
+        assert!(message.is_writable(compiled.accounts[2] as usize));
+        assert!(!message.is_writable(compiled.accounts[1] as usize));
+
+        let data = &compiled.data;
+        assert_eq!(data[..8], ix::Deposit { amount: 0 }.data()[..8]);
+        assert_eq!(
+            ix::Deposit::try_from_slice(&data[8..]).unwrap().amount,
+            1_000_000
+        );
+
+        let loaded = load_zero_copy::<MarginAccount, _>(&store, &keys[2]).unwrap();
+        assert_eq!({ loaded.balance }, 1_000_000);
+    }
+}
@@ -0,0 +1,160 @@
+//! Program derived addresses of the Zeta program.
+//!
+//! Seeds follow `zeta_cpi::zeta_constants`. Every helper returns the address
+//! together with its bump. `get_trade_ledger` and `get_oracle_ema` are the
+//! exceptions, they derive zeta-cpi's own accounts from `zeta_cpi::constants`.
+
+use anchor_lang::prelude::*;
+use zeta_cpi::constants::{ORACLE_EMA_SEED, TRADE_LEDGER_SEED};
+use zeta_cpi::zeta_constants::*;
+
+pub fn get_state(zeta_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STATE_SEED.as_bytes()], zeta_program)
+}
+
+pub fn get_zeta_group(zeta_program: &Pubkey, underlying_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ZETA_GROUP_SEED.as_bytes(), underlying_mint.as_ref()],
+        zeta_program,
+    )
+}
+
+pub fn get_greeks(zeta_program: &Pubkey, zeta_group: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[GREEKS_SEED.as_bytes(), zeta_group.as_ref()], zeta_program)
+}
+
+pub fn get_vault(zeta_program: &Pubkey, zeta_group: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED.as_bytes(), zeta_group.as_ref()], zeta_program)
+}
+
+pub fn get_socialized_loss_account(zeta_program: &Pubkey, zeta_group: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SOCIALIZED_LOSS_SEED.as_bytes(), zeta_group.as_ref()],
+        zeta_program,
+    )
+}
+
+pub fn get_insurance_vault(zeta_program: &Pubkey, zeta_group: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ZETA_INSURANCE_VAULT_SEED.as_bytes(), zeta_group.as_ref()],
+        zeta_program,
+    )
+}
+
+pub fn get_insurance_deposit_account(
+    zeta_program: &Pubkey,
+    zeta_group: &Pubkey,
+    authority: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            USER_INSURANCE_DEPOSIT_SEED.as_bytes(),
+            zeta_group.as_ref(),
+            authority.as_ref(),
+        ],
+        zeta_program,
+    )
+}
+
+pub fn get_whitelist_insurance_account(zeta_program: &Pubkey, authority: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[WHITELIST_INSURANCE_SEED.as_bytes(), authority.as_ref()],
+        zeta_program,
+    )
+}
+
+pub fn get_margin_account(
+    zeta_program: &Pubkey,
+    zeta_group: &Pubkey,
+    authority: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            MARGIN_SEED.as_bytes(),
+            zeta_group.as_ref(),
+            authority.as_ref(),
+        ],
+        zeta_program,
+    )
+}
+
+pub fn get_spread_account(
+    zeta_program: &Pubkey,
+    zeta_group: &Pubkey,
+    authority: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SPREAD_SEED.as_bytes(),
+            zeta_group.as_ref(),
+            authority.as_ref(),
+        ],
+        zeta_program,
+    )
+}
+
+pub fn get_open_orders(
+    zeta_program: &Pubkey,
+    dex_program: &Pubkey,
+    market: &Pubkey,
+    authority: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            OPEN_ORDERS_SEED.as_bytes(),
+            dex_program.as_ref(),
+            market.as_ref(),
+            authority.as_ref(),
+        ],
+        zeta_program,
+    )
+}
+
+pub fn get_open_orders_map(zeta_program: &Pubkey, open_orders: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[open_orders.as_ref()], zeta_program)
+}
+
+pub fn get_market_node(zeta_program: &Pubkey, zeta_group: &Pubkey, index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[MARKET_NODE_SEED.as_bytes(), zeta_group.as_ref(), &[index]],
+        zeta_program,
+    )
+}
+
+pub fn get_serum_authority(zeta_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SERUM_SEED.as_bytes()], zeta_program)
+}
+
+pub fn get_mint_authority(zeta_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MINT_AUTH_SEED.as_bytes()], zeta_program)
+}
+
+pub fn get_base_mint(zeta_program: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BASE_MINT_SEED.as_bytes(), market.as_ref()], zeta_program)
+}
+
+pub fn get_quote_mint(zeta_program: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[QUOTE_MINT_SEED.as_bytes(), market.as_ref()], zeta_program)
+}
+
+pub fn get_trade_ledger(zeta_cpi_program: &Pubkey, margin_account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[TRADE_LEDGER_SEED.as_bytes(), margin_account.as_ref()],
+        zeta_cpi_program,
+    )
+}
+
+pub fn get_oracle_ema(
+    zeta_cpi_program: &Pubkey,
+    oracle: &Pubkey,
+    window_seconds: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            ORACLE_EMA_SEED.as_bytes(),
+            oracle.as_ref(),
+            &window_seconds.to_le_bytes(),
+        ],
+        zeta_cpi_program,
+    )
+}
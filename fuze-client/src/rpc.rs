@@ -0,0 +1,152 @@
+//! Pluggable account access.
+//!
+//! Everything that reads chain state goes through `RpcProvider`, so the same
+//! code can run against a live cluster (`rpc-client` feature) or against an
+//! `InMemoryAccountStore` in tests.
+
+use crate::error::ClientError;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::Hash;
+use anchor_lang::Discriminator;
+use bytemuck::Pod;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use zeta_cpi::pyth_client;
+use zeta_cpi::serum_market::{MarketIncrements, MarketStateHeader};
+use zeta_cpi::zeta_constants::DISCRIMINATOR_SIZE;
+
+#[derive(Clone, Debug, Default)]
+pub struct AccountData {
+    pub lamports: u64,
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+    pub executable: bool,
+}
+
+pub trait RpcProvider {
+    /// Returns `None` if the account does not exist.
+    fn get_account(&self, pubkey: &Pubkey) -> std::result::Result<Option<AccountData>, ClientError>;
+
+    fn get_latest_blockhash(&self) -> std::result::Result<Hash, ClientError>;
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryAccountStore {
+    pub accounts: HashMap<Pubkey, AccountData>,
+    pub blockhash: Hash,
+}
+
+impl InMemoryAccountStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, pubkey: Pubkey, account: AccountData) {
+        self.accounts.insert(pubkey, account);
+    }
+
+    pub fn remove(&mut self, pubkey: &Pubkey) -> Option<AccountData> {
+        self.accounts.remove(pubkey)
+    }
+
+    /// Stores a zero copy account with its anchor discriminator.
+    pub fn insert_zero_copy<T: Pod + Discriminator>(
+        &mut self,
+        pubkey: Pubkey,
+        owner: Pubkey,
+        account: &T,
+    ) {
+        let mut data = T::discriminator().to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(account));
+        self.insert(
+            pubkey,
+            AccountData {
+                lamports: 0,
+                owner,
+                data,
+                executable: false,
+            },
+        );
+    }
+}
+
+impl RpcProvider for InMemoryAccountStore {
+    fn get_account(&self, pubkey: &Pubkey) -> std::result::Result<Option<AccountData>, ClientError> {
+        Ok(self.accounts.get(pubkey).cloned())
+    }
+
+    fn get_latest_blockhash(&self) -> std::result::Result<Hash, ClientError> {
+        Ok(self.blockhash)
+    }
+}
+
+#[cfg(feature = "rpc-client")]
+impl RpcProvider for solana_client::rpc_client::RpcClient {
+    fn get_account(&self, pubkey: &Pubkey) -> std::result::Result<Option<AccountData>, ClientError> {
+        let response = self
+            .get_account_with_commitment(pubkey, self.commitment())
+            .map_err(|e| ClientError::Rpc(e.to_string()))?;
+        Ok(response.value.map(|account| AccountData {
+            lamports: account.lamports,
+            owner: account.owner,
+            data: account.data,
+            executable: account.executable,
+        }))
+    }
+
+    fn get_latest_blockhash(&self) -> std::result::Result<Hash, ClientError> {
+        solana_client::rpc_client::RpcClient::get_latest_blockhash(self)
+            .map_err(|e| ClientError::Rpc(e.to_string()))
+    }
+}
+
+pub fn get_account_data<R: RpcProvider>(
+    rpc: &R,
+    pubkey: &Pubkey,
+) -> std::result::Result<AccountData, ClientError> {
+    rpc.get_account(pubkey)?
+        .ok_or(ClientError::AccountNotFound(*pubkey))
+}
+
+/// Loads a zero copy Zeta account, e.g. `ZetaGroup`, `Greeks` or `MarginAccount`.
+pub fn load_zero_copy<T: Pod + Discriminator, R: RpcProvider>(
+    rpc: &R,
+    pubkey: &Pubkey,
+) -> std::result::Result<Box<T>, ClientError> {
+    let account = get_account_data(rpc, pubkey)?;
+    let size = std::mem::size_of::<T>();
+    if account.data.len() < DISCRIMINATOR_SIZE + size {
+        return Err(ClientError::InvalidAccountData(*pubkey));
+    }
+    let discriminator: [u8; 8] = account.data[..DISCRIMINATOR_SIZE].try_into().unwrap();
+    if discriminator != T::discriminator() {
+        return Err(ClientError::InvalidAccountData(*pubkey));
+    }
+    Ok(Box::new(bytemuck::pod_read_unaligned(
+        &account.data[DISCRIMINATOR_SIZE..DISCRIMINATOR_SIZE + size],
+    )))
+}
+
+pub fn load_oracle<R: RpcProvider>(
+    rpc: &R,
+    pubkey: &Pubkey,
+) -> std::result::Result<Box<pyth_client::Price>, ClientError> {
+    let account = get_account_data(rpc, pubkey)?;
+    let size = std::mem::size_of::<pyth_client::Price>();
+    if account.data.len() < size {
+        return Err(ClientError::InvalidAccountData(*pubkey));
+    }
+    Ok(Box::new(bytemuck::pod_read_unaligned(&account.data[..size])))
+}
+
+/// Loads the tick and lot sizes of a DEX market, to preview the rounding of
+/// an order by the program with `MarketIncrements::round_order`.
+pub fn load_market_increments<R: RpcProvider>(
+    rpc: &R,
+    pubkey: &Pubkey,
+) -> std::result::Result<MarketIncrements, ClientError> {
+    let account = get_account_data(rpc, pubkey)?;
+    MarketStateHeader::from_account_data(&account.data)
+        .and_then(|header| MarketIncrements::from_header(&header))
+        .map_err(|_| ClientError::InvalidAccountData(*pubkey))
+}
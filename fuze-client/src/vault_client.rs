@@ -0,0 +1,501 @@
+//! Instruction builders for the put selling vault.
+//!
+//! The vault is built against an older anchor release than zeta-cpi, so it
+//! can't be linked in alongside it. The argument types below mirror
+//! `vault::context` and `instruction` mirrors `vault::instruction` byte for
+//! byte, encoded with the same `global:<name>` sighash anchor uses. The zeta
+//! accounts of the vault's CPIs are the generated `accounts` structs.
+
+use crate::accounts;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::Instruction;
+use zeta_cpi::zeta_account::Side;
+
+pub const VAULT_AUTHORITY_SEED: &str = "vault-authority";
+pub const REDEEMABLE_MINT_SEED: &str = "redeemable-mint";
+pub const VAULT_USDC_SEED: &str = "vault-usdc";
+pub const USER_REDEEMABLE_SEED: &str = "user-redeemable";
+
+#[derive(AnchorSerialize, AnchorDeserialize, Default, Clone, Copy, Debug)]
+pub struct EpochTimes {
+    pub start_epoch: i64,
+    pub end_deposits: i64,
+    pub start_auction: i64,
+    pub end_auction: i64,
+    pub start_settlement: i64,
+    pub end_epoch: i64,
+    pub epoch_cadence: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Default, Clone, Debug)]
+pub struct VaultBumps {
+    pub vault: u8,
+    pub vault_authority: u8,
+    pub redeemable_mint: u8,
+    pub vault_usdc: u8,
+}
+
+/// Derives the vault PDAs from its name.
+#[derive(Clone, Debug)]
+pub struct VaultAddresses {
+    pub vault: Pubkey,
+    pub vault_authority: Pubkey,
+    pub redeemable_mint: Pubkey,
+    pub vault_usdc: Pubkey,
+    pub bumps: VaultBumps,
+}
+
+impl VaultAddresses {
+    pub fn new(program_id: &Pubkey, vault_name: &str) -> Self {
+        let name = vault_name.as_bytes();
+        let (vault, vault_bump) = Pubkey::find_program_address(&[name], program_id);
+        let (vault_authority, vault_authority_bump) =
+            Pubkey::find_program_address(&[VAULT_AUTHORITY_SEED.as_bytes(), name], program_id);
+        let (redeemable_mint, redeemable_mint_bump) =
+            Pubkey::find_program_address(&[REDEEMABLE_MINT_SEED.as_bytes(), name], program_id);
+        let (vault_usdc, vault_usdc_bump) =
+            Pubkey::find_program_address(&[VAULT_USDC_SEED.as_bytes(), name], program_id);
+
+        Self {
+            vault,
+            vault_authority,
+            redeemable_mint,
+            vault_usdc,
+            bumps: VaultBumps {
+                vault: vault_bump,
+                vault_authority: vault_authority_bump,
+                redeemable_mint: redeemable_mint_bump,
+                vault_usdc: vault_usdc_bump,
+            },
+        }
+    }
+}
+
+pub fn get_user_redeemable(program_id: &Pubkey, vault_name: &str, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            USER_REDEEMABLE_SEED.as_bytes(),
+            vault_name.as_bytes(),
+            user.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+/// Arguments of a vault instruction, as anchor generates them in
+/// `vault::instruction`.
+pub trait VaultInstruction: AnchorSerialize {
+    const NAME: &'static str;
+
+    fn data(&self) -> Vec<u8> {
+        let preimage = format!("global:{}", Self::NAME);
+        let mut data = hash(preimage.as_bytes()).to_bytes()[..8].to_vec();
+        self.serialize(&mut data).unwrap();
+        data
+    }
+}
+
+macro_rules! vault_instruction {
+    ($name:ident, $ix_name:literal { $($field:ident: $ty:ty),* $(,)? }) => {
+        #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+        pub struct $name {
+            $(pub $field: $ty),*
+        }
+
+        impl VaultInstruction for $name {
+            const NAME: &'static str = $ix_name;
+        }
+    };
+}
+
+pub mod instruction {
+    use super::*;
+
+    vault_instruction!(InitializeVault, "initialize_vault" {
+        vault_name: String,
+        vault_lamports: u64,
+        bumps: VaultBumps,
+        epoch_times: EpochTimes,
+    });
+    vault_instruction!(
+        InitializeUserRedeemableTokenAccount,
+        "initialize_user_redeemable_token_account" {}
+    );
+    vault_instruction!(DepositVault, "deposit_vault" { bump: u8, usdc_amount: u64 });
+    vault_instruction!(WithdrawVault, "withdraw_vault" { bump: u8, redeemable_amount: u64 });
+    vault_instruction!(InitializeZetaMarginAccount, "initialize_zeta_margin_account" {});
+    vault_instruction!(DepositZeta, "deposit_zeta" { amount: u64 });
+    vault_instruction!(WithdrawZeta, "withdraw_zeta" { amount: u64 });
+    vault_instruction!(InitializeZetaOpenOrders, "initialize_zeta_open_orders" {});
+    // The vault's `Side` has the same borsh encoding as zeta-cpi's.
+    vault_instruction!(PlaceAuctionOrder, "place_auction_order" {
+        price: u64,
+        size: u64,
+        side: Side,
+        client_order_id: Option<u64>,
+    });
+    vault_instruction!(CancelAuctionOrder, "cancel_auction_order" { side: Side, order_id: u128 });
+    vault_instruction!(RolloverVault, "rollover_vault" {});
+}
+
+// Accounts shared by every vault instruction that wraps a zeta CPI.
+#[derive(Clone, Debug)]
+pub struct VaultAdminAccounts {
+    pub vault_admin: Pubkey,
+    pub vault: Pubkey,
+    pub usdc_mint: Pubkey,
+}
+
+#[derive(Clone, Debug)]
+pub struct InitializeVault {
+    pub vault_admin: Pubkey,
+    pub vault: Pubkey,
+    pub vault_authority: Pubkey,
+    pub usdc_mint: Pubkey,
+    pub redeemable_mint: Pubkey,
+    pub vault_usdc: Pubkey,
+    pub system_program: Pubkey,
+    pub token_program: Pubkey,
+    pub rent: Pubkey,
+}
+
+#[derive(Clone, Debug)]
+pub struct InitUserRedeemableTokenAccount {
+    pub user_authority: Pubkey,
+    pub user_redeemable: Pubkey,
+    pub vault: Pubkey,
+    pub vault_authority: Pubkey,
+    pub redeemable_mint: Pubkey,
+    pub system_program: Pubkey,
+    pub token_program: Pubkey,
+    pub rent: Pubkey,
+}
+
+// Used by both `deposit_vault` and `withdraw_vault`.
+#[derive(Clone, Debug)]
+pub struct VaultTransfer {
+    pub user_authority: Pubkey,
+    pub user_usdc: Pubkey,
+    pub user_redeemable: Pubkey,
+    pub vault: Pubkey,
+    pub vault_authority: Pubkey,
+    pub usdc_mint: Pubkey,
+    pub redeemable_mint: Pubkey,
+    pub vault_usdc: Pubkey,
+    pub token_program: Pubkey,
+}
+
+// The vault's open orders context has no separate payer, the PDA authority pays.
+#[derive(Clone, Debug)]
+pub struct InitializeZetaOpenOrders {
+    pub state: Pubkey,
+    pub zeta_group: Pubkey,
+    pub dex_program: Pubkey,
+    pub system_program: Pubkey,
+    pub open_orders: Pubkey,
+    pub margin_account: Pubkey,
+    pub authority: Pubkey,
+    pub market: Pubkey,
+    pub serum_authority: Pubkey,
+    pub open_orders_map: Pubkey,
+    pub rent: Pubkey,
+}
+
+impl ToAccountMetas for InitializeZetaOpenOrders {
+    fn to_account_metas(&self, _is_signer: Option<bool>) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.state, false),
+            AccountMeta::new_readonly(self.zeta_group, false),
+            AccountMeta::new_readonly(self.dex_program, false),
+            AccountMeta::new_readonly(self.system_program, false),
+            AccountMeta::new(self.open_orders, false),
+            AccountMeta::new(self.margin_account, false),
+            AccountMeta::new(self.authority, false),
+            AccountMeta::new_readonly(self.market, false),
+            AccountMeta::new_readonly(self.serum_authority, false),
+            AccountMeta::new(self.open_orders_map, false),
+            AccountMeta::new_readonly(self.rent, false),
+        ]
+    }
+}
+
+/// Builds instructions for the vault program.
+#[derive(Clone, Debug)]
+pub struct VaultClient {
+    pub program_id: Pubkey,
+    pub zeta_program: Pubkey,
+}
+
+impl VaultClient {
+    pub fn new(program_id: Pubkey, zeta_program: Pubkey) -> Self {
+        Self {
+            program_id,
+            zeta_program,
+        }
+    }
+
+    // The vault authority is a PDA and signs the zeta CPI via `invoke_signed`,
+    // so the signer flags the generated zeta accounts carry are cleared.
+    fn zeta_instruction(
+        &self,
+        admin: &VaultAdminAccounts,
+        cpi_accounts: &impl ToAccountMetas,
+        args: impl VaultInstruction,
+    ) -> Instruction {
+        let mut account_metas = vec![
+            AccountMeta::new_readonly(self.zeta_program, false),
+            AccountMeta::new_readonly(admin.vault_admin, true),
+            AccountMeta::new_readonly(admin.vault, false),
+            AccountMeta::new_readonly(admin.usdc_mint, false),
+        ];
+        account_metas.extend(
+            cpi_accounts
+                .to_account_metas(None)
+                .into_iter()
+                .map(|account_meta| AccountMeta {
+                    is_signer: false,
+                    ..account_meta
+                }),
+        );
+        Instruction {
+            program_id: self.program_id,
+            accounts: account_metas,
+            data: args.data(),
+        }
+    }
+
+    pub fn initialize_vault(
+        &self,
+        accounts: &InitializeVault,
+        vault_name: String,
+        vault_lamports: u64,
+        bumps: VaultBumps,
+        epoch_times: EpochTimes,
+    ) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(accounts.vault_admin, true),
+                AccountMeta::new(accounts.vault, false),
+                AccountMeta::new(accounts.vault_authority, false),
+                AccountMeta::new_readonly(accounts.usdc_mint, false),
+                AccountMeta::new(accounts.redeemable_mint, false),
+                AccountMeta::new(accounts.vault_usdc, false),
+                AccountMeta::new_readonly(accounts.system_program, false),
+                AccountMeta::new_readonly(accounts.token_program, false),
+                AccountMeta::new_readonly(accounts.rent, false),
+            ],
+            data: instruction::InitializeVault {
+                vault_name,
+                vault_lamports,
+                bumps,
+                epoch_times,
+            }
+            .data(),
+        }
+    }
+
+    pub fn initialize_user_redeemable_token_account(
+        &self,
+        accounts: &InitUserRedeemableTokenAccount,
+    ) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(accounts.user_authority, true),
+                AccountMeta::new(accounts.user_redeemable, false),
+                AccountMeta::new_readonly(accounts.vault, false),
+                AccountMeta::new_readonly(accounts.vault_authority, false),
+                AccountMeta::new_readonly(accounts.redeemable_mint, false),
+                AccountMeta::new_readonly(accounts.system_program, false),
+                AccountMeta::new_readonly(accounts.token_program, false),
+                AccountMeta::new_readonly(accounts.rent, false),
+            ],
+            data: instruction::InitializeUserRedeemableTokenAccount {}.data(),
+        }
+    }
+
+    fn vault_transfer_metas(accounts: &VaultTransfer, user_writable: bool) -> Vec<AccountMeta> {
+        let user_authority = if user_writable {
+            AccountMeta::new(accounts.user_authority, true)
+        } else {
+            AccountMeta::new_readonly(accounts.user_authority, true)
+        };
+        vec![
+            user_authority,
+            AccountMeta::new(accounts.user_usdc, false),
+            AccountMeta::new(accounts.user_redeemable, false),
+            AccountMeta::new_readonly(accounts.vault, false),
+            AccountMeta::new_readonly(accounts.vault_authority, false),
+            AccountMeta::new_readonly(accounts.usdc_mint, false),
+            AccountMeta::new(accounts.redeemable_mint, false),
+            AccountMeta::new(accounts.vault_usdc, false),
+            AccountMeta::new_readonly(accounts.token_program, false),
+        ]
+    }
+
+    pub fn deposit_vault(&self, accounts: &VaultTransfer, bump: u8, usdc_amount: u64) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: Self::vault_transfer_metas(accounts, false),
+            data: instruction::DepositVault { bump, usdc_amount }.data(),
+        }
+    }
+
+    pub fn withdraw_vault(
+        &self,
+        accounts: &VaultTransfer,
+        bump: u8,
+        redeemable_amount: u64,
+    ) -> Instruction {
+        // The user receives the redeemable account's rent once it is emptied.
+        Instruction {
+            program_id: self.program_id,
+            accounts: Self::vault_transfer_metas(accounts, true),
+            data: instruction::WithdrawVault {
+                bump,
+                redeemable_amount,
+            }
+            .data(),
+        }
+    }
+
+    pub fn initialize_zeta_margin_account(
+        &self,
+        admin: &VaultAdminAccounts,
+        cpi_accounts: &accounts::InitializeMarginAccount,
+    ) -> Instruction {
+        self.zeta_instruction(
+            admin,
+            cpi_accounts,
+            instruction::InitializeZetaMarginAccount {},
+        )
+    }
+
+    pub fn deposit_zeta(
+        &self,
+        admin: &VaultAdminAccounts,
+        cpi_accounts: &accounts::Deposit,
+        amount: u64,
+    ) -> Instruction {
+        self.zeta_instruction(admin, cpi_accounts, instruction::DepositZeta { amount })
+    }
+
+    pub fn withdraw_zeta(
+        &self,
+        admin: &VaultAdminAccounts,
+        cpi_accounts: &accounts::Withdraw,
+        amount: u64,
+    ) -> Instruction {
+        self.zeta_instruction(admin, cpi_accounts, instruction::WithdrawZeta { amount })
+    }
+
+    pub fn initialize_zeta_open_orders(
+        &self,
+        admin: &VaultAdminAccounts,
+        cpi_accounts: &InitializeZetaOpenOrders,
+    ) -> Instruction {
+        self.zeta_instruction(
+            admin,
+            cpi_accounts,
+            instruction::InitializeZetaOpenOrders {},
+        )
+    }
+
+    pub fn place_auction_order(
+        &self,
+        admin: &VaultAdminAccounts,
+        cpi_accounts: &accounts::PlaceOrder,
+        price: u64,
+        size: u64,
+        side: Side,
+        client_order_id: Option<u64>,
+    ) -> Instruction {
+        self.zeta_instruction(
+            admin,
+            cpi_accounts,
+            instruction::PlaceAuctionOrder {
+                price,
+                size,
+                side,
+                client_order_id,
+            },
+        )
+    }
+
+    pub fn cancel_auction_order(
+        &self,
+        admin: &VaultAdminAccounts,
+        cpi_accounts: &accounts::CancelOrder,
+        side: Side,
+        order_id: u128,
+    ) -> Instruction {
+        self.zeta_instruction(
+            admin,
+            cpi_accounts,
+            instruction::CancelAuctionOrder { side, order_id },
+        )
+    }
+
+    pub fn rollover_vault(&self, vault_admin: Pubkey, vault: Pubkey) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(vault_admin, true),
+                AccountMeta::new(vault, false),
+            ],
+            data: instruction::RolloverVault {}.data(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::InstructionData;
+    use zeta_cpi::instruction as ix;
+
+    vault_instruction!(Deposit, "deposit" { amount: u64 });
+
+    #[test]
+    fn test_instruction_data_matches_anchor() {
+        assert_eq!(
+            Deposit { amount: 42 }.data(),
+            ix::Deposit { amount: 42 }.data()
+        );
+    }
+
+    #[test]
+    fn test_zeta_instruction_signers() {
+        let admin = VaultAdminAccounts {
+            vault_admin: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            usdc_mint: Pubkey::new_unique(),
+        };
+        let cpi_accounts = accounts::Deposit {
+            zeta_group: Pubkey::new_unique(),
+            margin_account: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            user_token_account: Pubkey::new_unique(),
+            socialized_loss_account: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            token_program: Pubkey::new_unique(),
+            state: Pubkey::new_unique(),
+            greeks: Pubkey::new_unique(),
+        };
+        let client = VaultClient::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let instruction = client.deposit_zeta(&admin, &cpi_accounts, 1_000_000);
+
+        // Only the vault admin signs, the vault authority PDA signs the CPI.
+        let signers: Vec<Pubkey> = instruction
+            .accounts
+            .iter()
+            .filter(|account_meta| account_meta.is_signer)
+            .map(|account_meta| account_meta.pubkey)
+            .collect();
+        assert_eq!(signers, vec![admin.vault_admin]);
+        assert_eq!(instruction.accounts[4].pubkey, cpi_accounts.zeta_group);
+        assert!(instruction.accounts[5].is_writable);
+    }
+}
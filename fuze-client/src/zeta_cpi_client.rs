@@ -0,0 +1,456 @@
+use crate::accounts;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::InstructionData;
+use zeta_cpi::context::{OrderSpec, PositionMovementArg};
+use zeta_cpi::instruction as ix;
+use zeta_cpi::oracle::PriceMode;
+use zeta_cpi::units::{Lots, NativePrice};
+use zeta_cpi::zeta_account::{MovementType, OrderType, Side};
+
+/// Builds instructions for the zeta-cpi program.
+///
+/// Every `*Caller` context takes the zeta program first, followed by the
+/// zeta accounts for the wrapped instruction, which callers pass as the
+/// nested context (see `accounts`).
+#[derive(Clone, Debug)]
+pub struct ZetaCpiClient {
+    pub program_id: Pubkey,
+    pub zeta_program: Pubkey,
+}
+
+impl ZetaCpiClient {
+    pub fn new(program_id: Pubkey, zeta_program: Pubkey) -> Self {
+        Self {
+            program_id,
+            zeta_program,
+        }
+    }
+
+    fn caller_instruction(
+        &self,
+        accounts: &impl ToAccountMetas,
+        data: impl InstructionData,
+    ) -> Instruction {
+        let mut account_metas = vec![AccountMeta::new_readonly(self.zeta_program, false)];
+        account_metas.extend(accounts.to_account_metas(None));
+        Instruction {
+            program_id: self.program_id,
+            accounts: account_metas,
+            data: data.data(),
+        }
+    }
+
+    pub fn initialize_margin_account(
+        &self,
+        accounts: &accounts::InitializeMarginAccount,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::InitializeMarginAccount {})
+    }
+
+    pub fn initialize_spread_account(
+        &self,
+        accounts: &accounts::InitializeSpreadAccount,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::InitializeSpreadAccount {})
+    }
+
+    pub fn deposit(&self, accounts: &accounts::Deposit, amount: u64) -> Instruction {
+        self.caller_instruction(accounts, ix::Deposit { amount })
+    }
+
+    pub fn withdraw(&self, accounts: &accounts::Withdraw, amount: u64) -> Instruction {
+        self.caller_instruction(accounts, ix::Withdraw { amount })
+    }
+
+    /// `nonce` is the bump of `pda::get_insurance_deposit_account`.
+    pub fn initialize_insurance_deposit_account(
+        &self,
+        accounts: &accounts::InitializeInsuranceDepositAccount,
+        nonce: u8,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::InitializeInsuranceDepositAccount { nonce })
+    }
+
+    pub fn deposit_insurance_vault(
+        &self,
+        accounts: &accounts::DepositInsuranceVault,
+        amount: u64,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::DepositInsuranceVault { amount })
+    }
+
+    pub fn withdraw_insurance_vault(
+        &self,
+        accounts: &accounts::WithdrawInsuranceVault,
+        percentage_amount: u64,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::WithdrawInsuranceVault { percentage_amount })
+    }
+
+    pub fn initialize_open_orders(&self, accounts: &accounts::InitializeOpenOrders) -> Instruction {
+        self.caller_instruction(accounts, ix::InitializeOpenOrders {})
+    }
+
+    pub fn close_margin_account(&self, accounts: &accounts::CloseMarginAccount) -> Instruction {
+        self.caller_instruction(accounts, ix::CloseMarginAccount {})
+    }
+
+    pub fn close_spread_account(&self, accounts: &accounts::CloseSpreadAccount) -> Instruction {
+        self.caller_instruction(accounts, ix::CloseSpreadAccount {})
+    }
+
+    /// `map_nonce` is the bump of `accounts.open_orders_map`, see
+    /// `pda::get_open_orders_map`.
+    pub fn close_open_orders(
+        &self,
+        accounts: &accounts::CloseOpenOrders,
+        map_nonce: u8,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::CloseOpenOrders { map_nonce })
+    }
+
+    pub fn initialize_trade_ledger(
+        &self,
+        accounts: &accounts::InitializeTradeLedger,
+    ) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: accounts.to_account_metas(None),
+            data: ix::InitializeTradeLedger {}.data(),
+        }
+    }
+
+    /// `accounts.oracle_ema` is derived by `pda::get_oracle_ema` for the same
+    /// `window_seconds`.
+    pub fn initialize_oracle_ema(
+        &self,
+        accounts: &accounts::InitializeOracleEma,
+        window_seconds: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: accounts.to_account_metas(None),
+            data: ix::InitializeOracleEma { window_seconds }.data(),
+        }
+    }
+
+    pub fn update_oracle_ema(&self, accounts: &accounts::UpdateOracleEma) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: accounts.to_account_metas(None),
+            data: ix::UpdateOracleEma {}.data(),
+        }
+    }
+
+    /// Taker fills are recorded in `trade_ledger` when given, see
+    /// `pda::get_trade_ledger`.
+    pub fn place_order(
+        &self,
+        accounts: &accounts::PlaceOrder,
+        trade_ledger: Option<Pubkey>,
+        price: NativePrice,
+        size: Lots,
+        side: Side,
+        client_order_id: Option<u64>,
+    ) -> Instruction {
+        let mut instruction = self.caller_instruction(
+            accounts,
+            ix::PlaceOrder {
+                price: price.get(),
+                size: size.get(),
+                side,
+                client_order_id,
+            },
+        );
+        instruction
+            .accounts
+            .extend(trade_ledger.map(|trade_ledger| AccountMeta::new(trade_ledger, false)));
+        instruction
+    }
+
+    pub fn place_order_v3(
+        &self,
+        accounts: &accounts::PlaceOrder,
+        trade_ledger: Option<Pubkey>,
+        price: NativePrice,
+        size: Lots,
+        side: Side,
+        order_type: OrderType,
+        client_order_id: Option<u64>,
+        tag: Option<String>,
+    ) -> Instruction {
+        let mut instruction = self.caller_instruction(
+            accounts,
+            ix::PlaceOrderV3 {
+                price: price.get(),
+                size: size.get(),
+                side,
+                order_type,
+                client_order_id,
+                tag,
+            },
+        );
+        instruction
+            .accounts
+            .extend(trade_ledger.map(|trade_ledger| AccountMeta::new(trade_ledger, false)));
+        instruction
+    }
+
+    /// An order given a `tif_offset` expires that many seconds after it is
+    /// placed, the offset can't run past the expiry of the market's series.
+    pub fn place_order_v4(
+        &self,
+        accounts: &accounts::PlaceOrder,
+        trade_ledger: Option<Pubkey>,
+        price: NativePrice,
+        size: Lots,
+        side: Side,
+        order_type: OrderType,
+        client_order_id: Option<u64>,
+        tag: Option<String>,
+        tif_offset: Option<u16>,
+    ) -> Instruction {
+        let mut instruction = self.caller_instruction(
+            accounts,
+            ix::PlaceOrderV4 {
+                price: price.get(),
+                size: size.get(),
+                side,
+                order_type,
+                client_order_id,
+                tag,
+                tif_offset,
+            },
+        );
+        instruction
+            .accounts
+            .extend(trade_ledger.map(|trade_ledger| AccountMeta::new(trade_ledger, false)));
+        instruction
+    }
+
+    /// `accounts.zeta_program` is the zeta program, as `PlaceOrdersCaller`
+    /// holds the shared accounts itself. Needs one `market_groups` entry per
+    /// market traded by `orders`, taker fills are recorded in `trade_ledger`
+    /// when given.
+    /// `compute_unit_limit` is the limit requested for the transaction with a
+    /// compute budget instruction, orders past what it fits (3 at the default
+    /// 200k) are skipped and the number placed is written to the return data
+    /// as a `u32`. Passing more than the transaction requests fails it.
+    pub fn place_orders(
+        &self,
+        accounts: &accounts::PlaceOrdersCaller,
+        market_groups: &[accounts::PlaceOrdersMarketGroup],
+        trade_ledger: Option<Pubkey>,
+        orders: Vec<OrderSpec>,
+        compute_unit_limit: Option<u32>,
+    ) -> Instruction {
+        let mut instruction = Instruction {
+            program_id: self.program_id,
+            accounts: accounts.to_account_metas(None),
+            data: ix::PlaceOrders {
+                orders,
+                compute_unit_limit,
+            }
+            .data(),
+        };
+        for market_group in market_groups {
+            instruction
+                .accounts
+                .extend(market_group.to_account_metas(None));
+        }
+        instruction
+            .accounts
+            .extend(trade_ledger.map(|trade_ledger| AccountMeta::new(trade_ledger, false)));
+        instruction
+    }
+
+    pub fn cancel_order(
+        &self,
+        accounts: &accounts::CancelOrder,
+        side: Side,
+        order_id: u128,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::CancelOrder { side, order_id })
+    }
+
+    pub fn cancel_order_by_client_order_id(
+        &self,
+        accounts: &accounts::CancelOrder,
+        client_order_id: u64,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::CancelOrderByClientOrderId { client_order_id })
+    }
+
+    /// Cancels `cancel_client_order_id` and places the replacement order in
+    /// one instruction. Both account groups must share market and margin account.
+    pub fn cancel_replace(
+        &self,
+        cancel_accounts: &accounts::CancelOrder,
+        place_accounts: &accounts::PlaceOrder,
+        trade_ledger: Option<Pubkey>,
+        cancel_client_order_id: u64,
+        price: NativePrice,
+        size: Lots,
+        side: Side,
+        order_type: OrderType,
+        client_order_id: Option<u64>,
+        tag: Option<String>,
+    ) -> Instruction {
+        let mut instruction = self.caller_instruction(
+            cancel_accounts,
+            ix::CancelReplace {
+                cancel_client_order_id,
+                price: price.get(),
+                size: size.get(),
+                side,
+                order_type,
+                client_order_id,
+                tag,
+            },
+        );
+        instruction
+            .accounts
+            .extend(place_accounts.to_account_metas(None));
+        instruction
+            .accounts
+            .extend(trade_ledger.map(|trade_ledger| AccountMeta::new(trade_ledger, false)));
+        instruction
+    }
+
+    pub fn cancel_all_market_orders(&self, accounts: &accounts::CancelOrder) -> Instruction {
+        self.caller_instruction(accounts, ix::CancelAllMarketOrders {})
+    }
+
+    /// Permissionless, the margin account must be flagged for force
+    /// cancellation or below maintenance margin including orders.
+    pub fn force_cancel_orders(&self, accounts: &accounts::ForceCancelOrders) -> Instruction {
+        self.caller_instruction(accounts, ix::ForceCancelOrders {})
+    }
+
+    /// `market_index` has to be the index of `accounts.market`.
+    pub fn liquidate(
+        &self,
+        accounts: &accounts::Liquidate,
+        size: u64,
+        market_index: u8,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::Liquidate { size, market_index })
+    }
+
+    /// `open_orders` are the accounts referenced by the queued events.
+    pub fn crank_event_queue(
+        &self,
+        accounts: &accounts::CrankEventQueue,
+        open_orders: &[Pubkey],
+    ) -> Instruction {
+        let mut instruction = self.caller_instruction(accounts, ix::CrankEventQueue {});
+        instruction.accounts.extend(
+            open_orders
+                .iter()
+                .map(|open_orders| AccountMeta::new(*open_orders, false)),
+        );
+        instruction
+    }
+
+    pub fn settle_dex_funds(
+        &self,
+        accounts: &accounts::SettleDexFunds,
+        open_orders: &[Pubkey],
+    ) -> Instruction {
+        let mut instruction = self.caller_instruction(accounts, ix::SettleDexFunds {});
+        instruction.accounts.extend(
+            open_orders
+                .iter()
+                .map(|open_orders| AccountMeta::new(*open_orders, false)),
+        );
+        instruction
+    }
+
+    /// `oracle_ema` is the reference price of `PriceMode::Twap` and
+    /// `PriceMode::Conservative`, the oracle's own TWAP is used without it.
+    pub fn read_program_data(
+        &self,
+        accounts: &accounts::ReadProgramData,
+        oracle_ema: Option<Pubkey>,
+        price_mode: PriceMode,
+    ) -> Instruction {
+        let mut accounts = accounts.to_account_metas(None);
+        accounts.extend(oracle_ema.map(|oracle_ema| AccountMeta::new_readonly(oracle_ema, false)));
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: ix::ReadProgramData { price_mode }.data(),
+        }
+    }
+
+    /// The projected `MarginAccountState` is written to the transaction's
+    /// return data. `oracle_ema` is used as for `read_program_data`.
+    pub fn simulate_order(
+        &self,
+        accounts: &accounts::SimulateOrder,
+        oracle_ema: Option<Pubkey>,
+        market_index: u8,
+        side: Side,
+        price: NativePrice,
+        size: Lots,
+        price_mode: PriceMode,
+    ) -> Instruction {
+        let mut accounts = accounts.to_account_metas(None);
+        accounts.extend(oracle_ema.map(|oracle_ema| AccountMeta::new_readonly(oracle_ema, false)));
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: ix::SimulateOrder {
+                market_index,
+                side,
+                price: price.get(),
+                size: size.get(),
+                price_mode,
+            }
+            .data(),
+        }
+    }
+
+    /// The keys of the force cancellable accounts among `margin_accounts`
+    /// are written to the transaction's return data as a `Vec<Pubkey>`.
+    pub fn find_force_cancellable_accounts(
+        &self,
+        accounts: &accounts::FindForceCancellableAccounts,
+        margin_accounts: &[Pubkey],
+    ) -> Instruction {
+        let mut account_metas = accounts.to_account_metas(None);
+        account_metas.extend(
+            margin_accounts
+                .iter()
+                .map(|margin_account| AccountMeta::new_readonly(*margin_account, false)),
+        );
+        Instruction {
+            program_id: self.program_id,
+            accounts: account_metas,
+            data: ix::FindForceCancellableAccounts {}.data(),
+        }
+    }
+
+    pub fn position_movement(
+        &self,
+        accounts: &accounts::PositionMovement,
+        movement_type: MovementType,
+        movements: Vec<PositionMovementArg>,
+    ) -> Instruction {
+        self.caller_instruction(
+            accounts,
+            ix::PositionMovement {
+                movement_type,
+                movements,
+            },
+        )
+    }
+
+    pub fn transfer_excess_spread_balance(
+        &self,
+        accounts: &accounts::TransferExcessSpreadBalance,
+    ) -> Instruction {
+        self.caller_instruction(accounts, ix::TransferExcessSpreadBalance {})
+    }
+}
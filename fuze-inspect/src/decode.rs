@@ -0,0 +1,247 @@
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use bytemuck::{Pod, Zeroable};
+use std::convert::TryInto;
+use std::mem::size_of;
+use zeta_cpi::pyth_client::Price;
+use zeta_cpi::zeta_account::*;
+use zeta_cpi::zeta_constants::DISCRIMINATOR_SIZE;
+
+// Pyth accounts carry no anchor discriminator, they are identified by magic.
+pub const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+
+pub enum ZetaAccount {
+    State(Box<State>),
+    ZetaGroup(Box<ZetaGroup>),
+    Greeks(Box<Greeks>),
+    MarginAccount(Box<MarginAccount>),
+    SpreadAccount(Box<SpreadAccount>),
+    Oracle(Box<Price>),
+}
+
+impl ZetaAccount {
+    /// Detects the account layout from the data and decodes it.
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        if data.len() >= 4 && u32::from_le_bytes(data[..4].try_into().unwrap()) == PYTH_MAGIC {
+            return Ok(ZetaAccount::Oracle(read_pod(data)?));
+        }
+
+        if data.len() < DISCRIMINATOR_SIZE {
+            return Err(format!("account data too short ({} bytes)", data.len()));
+        }
+        let discriminator: [u8; 8] = data[..DISCRIMINATOR_SIZE].try_into().unwrap();
+        let body = &data[DISCRIMINATOR_SIZE..];
+
+        if discriminator == State::discriminator() {
+            // State is borsh serialized, trailing fields we don't mirror are ignored.
+            let state = State::deserialize(&mut &body[..])
+                .map_err(|err| format!("invalid State data: {}", err))?;
+            Ok(ZetaAccount::State(Box::new(state)))
+        } else if discriminator == ZetaGroup::discriminator() {
+            Ok(ZetaAccount::ZetaGroup(read_pod(body)?))
+        } else if discriminator == Greeks::discriminator() {
+            Ok(ZetaAccount::Greeks(read_pod(body)?))
+        } else if discriminator == MarginAccount::discriminator() {
+            Ok(ZetaAccount::MarginAccount(read_pod(body)?))
+        } else if discriminator == SpreadAccount::discriminator() {
+            Ok(ZetaAccount::SpreadAccount(read_pod(body)?))
+        } else {
+            Err(format!("unknown account discriminator {:?}", discriminator))
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ZetaAccount::State(_) => "State",
+            ZetaAccount::ZetaGroup(_) => "ZetaGroup",
+            ZetaAccount::Greeks(_) => "Greeks",
+            ZetaAccount::MarginAccount(_) => "MarginAccount",
+            ZetaAccount::SpreadAccount(_) => "SpreadAccount",
+            ZetaAccount::Oracle(_) => "Oracle",
+        }
+    }
+}
+
+/// A layout decoded into one of the `ZetaAccount` variants.
+pub trait ZetaAccountType: Sized {
+    fn from_account(account: &ZetaAccount) -> Option<&Self>;
+}
+
+macro_rules! zeta_account_type {
+    ($ty:ty, $variant:ident) => {
+        impl ZetaAccountType for $ty {
+            fn from_account(account: &ZetaAccount) -> Option<&Self> {
+                match account {
+                    ZetaAccount::$variant(inner) => Some(inner),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+zeta_account_type!(State, State);
+zeta_account_type!(ZetaGroup, ZetaGroup);
+zeta_account_type!(Greeks, Greeks);
+zeta_account_type!(MarginAccount, MarginAccount);
+zeta_account_type!(SpreadAccount, SpreadAccount);
+zeta_account_type!(Price, Oracle);
+
+/// Byte offset of a field of `$ty`, taken on a zeroed value as the zero copy
+/// accounts are packed.
+macro_rules! offset_of {
+    ($ty:ty, $($field:tt)+) => {{
+        let base = Box::new(<$ty as Zeroable>::zeroed());
+        std::ptr::addr_of!((*base).$($field)+) as usize - &*base as *const $ty as usize
+    }};
+}
+
+const BOOL_VALUES: &[u8] = &[0, 1];
+const KIND_VALUES: &[u8] = &[
+    Kind::Uninitialized as u8,
+    Kind::Call as u8,
+    Kind::Put as u8,
+    Kind::Future as u8,
+    Kind::Perp as u8,
+];
+const ASSET_VALUES: &[u8] = &[
+    Asset::SOL as u8,
+    Asset::BTC as u8,
+    Asset::ETH as u8,
+    Asset::UNDEFINED as u8,
+];
+const MARGIN_ACCOUNT_TYPE_VALUES: &[u8] = &[
+    MarginAccountType::Normal as u8,
+    MarginAccountType::MarketMaker as u8,
+];
+
+/// An account layout read with `pod_read_unaligned`.
+///
+/// Enum and bool fields only have a few valid values and reading any other
+/// byte into them is undefined behaviour, so a dump is checked against
+/// `tagged_bytes` before it is read.
+trait PodAccount: Pod {
+    /// Offsets of the enum and bool bytes with the values valid there.
+    fn tagged_bytes() -> Vec<(usize, &'static [u8])> {
+        Vec::new()
+    }
+}
+
+// Pyth enums are mirrored as integers, see `pyth_client::Price`.
+impl PodAccount for Price {}
+
+impl PodAccount for Greeks {}
+
+impl PodAccount for ZetaGroup {
+    fn tagged_bytes() -> Vec<(usize, &'static [u8])> {
+        // HaltState fields are private: a bool and two u64s (17 bytes), then bool arrays.
+        let halt_state = offset_of!(ZetaGroup, halt_state);
+        let mut tagged_bytes: Vec<(usize, &'static [u8])> = std::iter::once(halt_state)
+            .chain(halt_state + 17..halt_state + size_of::<HaltState>())
+            .map(|offset| (offset, BOOL_VALUES))
+            .collect();
+
+        // `products` and `products_padding` are contiguous, as are the expiry series.
+        let products = offset_of!(ZetaGroup, products);
+        let num_products = (offset_of!(ZetaGroup, expiry_series) - products) / size_of::<Product>();
+        let strike_is_set = offset_of!(ZetaGroup, products[0].strike.is_set) - products;
+        let dirty = offset_of!(ZetaGroup, products[0].dirty) - products;
+        let kind = offset_of!(ZetaGroup, products[0].kind) - products;
+        for i in 0..num_products {
+            let product = products + i * size_of::<Product>();
+            tagged_bytes.push((product + strike_is_set, BOOL_VALUES));
+            tagged_bytes.push((product + dirty, BOOL_VALUES));
+            tagged_bytes.push((product + kind, KIND_VALUES));
+        }
+
+        let expiry_series = offset_of!(ZetaGroup, expiry_series);
+        let num_expiry_series = (offset_of!(ZetaGroup, total_insurance_vault_deposits)
+            - expiry_series)
+            / size_of::<ExpirySeries>();
+        let series_dirty = offset_of!(ZetaGroup, expiry_series[0].dirty) - expiry_series;
+        for i in 0..num_expiry_series {
+            tagged_bytes.push((
+                expiry_series + i * size_of::<ExpirySeries>() + series_dirty,
+                BOOL_VALUES,
+            ));
+        }
+
+        tagged_bytes.push((offset_of!(ZetaGroup, asset), ASSET_VALUES));
+        tagged_bytes
+    }
+}
+
+impl PodAccount for MarginAccount {
+    fn tagged_bytes() -> Vec<(usize, &'static [u8])> {
+        vec![
+            (offset_of!(MarginAccount, force_cancel_flag), BOOL_VALUES),
+            (offset_of!(MarginAccount, asset), ASSET_VALUES),
+            (
+                offset_of!(MarginAccount, account_type),
+                MARGIN_ACCOUNT_TYPE_VALUES,
+            ),
+        ]
+    }
+}
+
+impl PodAccount for SpreadAccount {
+    fn tagged_bytes() -> Vec<(usize, &'static [u8])> {
+        vec![(offset_of!(SpreadAccount, asset), ASSET_VALUES)]
+    }
+}
+
+fn read_pod<T: PodAccount>(data: &[u8]) -> Result<Box<T>, String> {
+    let size = size_of::<T>();
+    if data.len() < size {
+        return Err(format!(
+            "expected at least {} bytes of account data, got {}",
+            size,
+            data.len()
+        ));
+    }
+    for (offset, values) in T::tagged_bytes() {
+        if !values.contains(&data[offset]) {
+            return Err(format!(
+                "invalid enum or bool value {} at offset {}",
+                data[offset], offset
+            ));
+        }
+    }
+    Ok(Box::new(bytemuck::pod_read_unaligned(&data[..size])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn margin_account_data(account_type: u8) -> Vec<u8> {
+        let mut data = MarginAccount::discriminator().to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(&MarginAccount::zeroed()));
+        data[DISCRIMINATOR_SIZE + offset_of!(MarginAccount, account_type)] = account_type;
+        data
+    }
+
+    #[test]
+    fn test_decode_validates_enum_bytes() {
+        let data = margin_account_data(MarginAccountType::MarketMaker as u8);
+        match ZetaAccount::decode(&data) {
+            Ok(ZetaAccount::MarginAccount(margin_account)) => {
+                assert_eq!(
+                    { margin_account.account_type },
+                    MarginAccountType::MarketMaker
+                );
+            }
+            _ => panic!("expected a margin account"),
+        }
+
+        assert!(ZetaAccount::decode(&margin_account_data(2)).is_err());
+    }
+
+    #[test]
+    fn test_zeta_group_tagged_bytes() {
+        let tagged_bytes = ZetaGroup::tagged_bytes();
+        let size = size_of::<ZetaGroup>();
+        assert!(tagged_bytes.iter().all(|(offset, _)| *offset < size));
+        // Halt state bools, 3 bytes per product, expiry series and the asset.
+        assert_eq!(tagged_bytes.len(), 1 + 150 + 138 * 3 + 6 + 1);
+    }
+}
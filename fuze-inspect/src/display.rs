@@ -0,0 +1,501 @@
+use crate::decode::*;
+use crate::snapshot::*;
+use rust_decimal::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeta_cpi::oracle::*;
+use zeta_cpi::pyth_client::{Price, PriceStatus};
+use zeta_cpi::units::NativePrice;
+use zeta_cpi::zeta_account::*;
+use zeta_cpi::zeta_basis::*;
+use zeta_cpi::zeta_calculations::*;
+use zeta_cpi::zeta_constants::*;
+use zeta_cpi::zeta_utils::*;
+
+/// Formats a fixed point integer with the given number of decimals.
+fn fixed(value: impl Into<i128>, precision: u32) -> Decimal {
+    Decimal::from_i128_with_scale(value.into(), precision)
+}
+
+/// Formats a margin parameter, where `NATIVE_PRECISION_DENOMINATOR` is 100%.
+fn percentage(value: u64) -> String {
+    format!("{}%", fixed(value, 6))
+}
+
+pub fn print_account(path: &str, snapshot: &AccountSnapshot, account: &ZetaAccount) {
+    println!("== {} ({})", path, account.name());
+    if let Some(pubkey) = snapshot.pubkey {
+        println!("pubkey: {}", pubkey);
+    }
+    if let Some(owner) = snapshot.owner {
+        println!("owner: {}", owner);
+    }
+    println!("data length: {}", snapshot.data.len());
+
+    match account {
+        ZetaAccount::State(state) => print_state(state),
+        ZetaAccount::ZetaGroup(zeta_group) => print_zeta_group(zeta_group),
+        ZetaAccount::Greeks(greeks) => print_greeks(greeks),
+        ZetaAccount::MarginAccount(margin_account) => print_margin_account(margin_account),
+        ZetaAccount::SpreadAccount(spread_account) => print_spread_account(spread_account),
+        ZetaAccount::Oracle(price) => print_oracle(price),
+    }
+    println!();
+}
+
+fn print_state(state: &State) {
+    println!("admin: {}", state.admin);
+    println!("num underlyings: {}", state.num_underlyings);
+    println!("expiry interval seconds: {}", state.expiry_interval_seconds);
+    println!(
+        "native trade fee percentage: {}",
+        percentage(state.native_trade_fee_percentage)
+    );
+    println!(
+        "native underlying fee percentage: {}",
+        percentage(state.native_underlying_fee_percentage)
+    );
+    println!(
+        "native deposit limit: {}",
+        fixed(state.native_deposit_limit, PLATFORM_PRECISION)
+    );
+    println!(
+        "position movement fee bps: {}",
+        state.position_movement_fee_bps
+    );
+    println!(
+        "margin concession percentage: {}%",
+        state.margin_concession_percentage
+    );
+}
+
+fn print_zeta_group(zeta_group: &ZetaGroup) {
+    println!("asset: {:?}", { zeta_group.asset });
+    println!("underlying mint: {}", { zeta_group.underlying_mint });
+    println!("oracle: {}", { zeta_group.oracle });
+    println!("greeks: {}", { zeta_group.greeks });
+    println!("front expiry index: {}", { zeta_group.front_expiry_index });
+    println!(
+        "total insurance vault deposits: {}",
+        fixed({ zeta_group.total_insurance_vault_deposits }, PLATFORM_PRECISION)
+    );
+    println!(
+        "expiry interval seconds: {}",
+        { zeta_group.expiry_interval_seconds }
+    );
+
+    let margin_parameters = zeta_group.margin_parameters;
+    println!("margin parameters:");
+    println!(
+        "  future initial / maintenance: {} / {}",
+        percentage(margin_parameters.future_margin_initial),
+        percentage(margin_parameters.future_margin_maintenance)
+    );
+    println!(
+        "  option long initial (mark / spot): {} / {}",
+        percentage(margin_parameters.option_mark_percentage_long_initial),
+        percentage(margin_parameters.option_spot_percentage_long_initial)
+    );
+    println!(
+        "  option short initial (spot / dynamic): {} / {}",
+        percentage(margin_parameters.option_spot_percentage_short_initial),
+        percentage(margin_parameters.option_dynamic_percentage_short_initial)
+    );
+    println!(
+        "  option long maintenance (mark / spot): {} / {}",
+        percentage(margin_parameters.option_mark_percentage_long_maintenance),
+        percentage(margin_parameters.option_spot_percentage_long_maintenance)
+    );
+    println!(
+        "  option short maintenance (spot / dynamic): {} / {}",
+        percentage(margin_parameters.option_spot_percentage_short_maintenance),
+        percentage(margin_parameters.option_dynamic_percentage_short_maintenance)
+    );
+    println!(
+        "  option short put cap: {}",
+        percentage(margin_parameters.option_short_put_cap_percentage)
+    );
+
+    for expiry_index in 0..ACTIVE_EXPIRIES {
+        let expiry_series = zeta_group.expiry_series[expiry_index];
+        println!(
+            "expiry series {}: active_ts={} expiry_ts={} dirty={}",
+            expiry_index,
+            { expiry_series.active_ts },
+            { expiry_series.expiry_ts },
+            { expiry_series.dirty }
+        );
+        for (i, product) in zeta_group.get_products_slice(expiry_index).iter().enumerate() {
+            let strike = match product.strike.get_strike() {
+                Ok(strike) => fixed(strike, PLATFORM_PRECISION).to_string(),
+                Err(_) => "unset".to_string(),
+            };
+            println!(
+                "  [{}] {:?} strike={} market={} dirty={}",
+                get_products_slice_market_index(expiry_index, i),
+                { product.kind },
+                strike,
+                { product.market },
+                { product.dirty }
+            );
+        }
+    }
+}
+
+fn print_greeks(greeks: &Greeks) {
+    let mark_prices = { greeks.mark_prices };
+    let update_timestamp = { greeks.update_timestamp };
+    let interest_rate = { greeks.interest_rate };
+    let volatility = { greeks.volatility };
+
+    for expiry_index in 0..ACTIVE_EXPIRIES {
+        println!(
+            "expiry {}: updated={} interest rate={}",
+            expiry_index,
+            update_timestamp[expiry_index],
+            fixed(interest_rate[expiry_index], PRICING_PRECISION)
+        );
+        let head = expiry_index * VOLATILITY_POINTS;
+        let nodes: Vec<String> = volatility[head..head + VOLATILITY_POINTS]
+            .iter()
+            .map(|v| fixed(*v, PRICING_PRECISION).to_string())
+            .collect();
+        println!("  volatility nodes: [{}]", nodes.join(", "));
+
+        let product_greeks = greeks.get_product_greeks_slice(expiry_index);
+        for i in 0..NUM_PRODUCTS_PER_SERIES {
+            let market_index = get_products_slice_market_index(expiry_index, i);
+            let mark = fixed(mark_prices[market_index], PLATFORM_PRECISION);
+            if i == SERIES_FUTURE_INDEX {
+                println!("  [{}] future mark={}", market_index, mark);
+                continue;
+            }
+            // Calls and puts of the same strike share a greeks entry, which
+            // holds the call delta. A put's delta is the call delta - 1.
+            let strike_greeks = product_greeks[i % NUM_STRIKES];
+            let mut delta = fixed({ strike_greeks.delta }, PRICING_PRECISION);
+            if i >= NUM_STRIKES {
+                delta -= Decimal::ONE;
+            }
+            println!(
+                "  [{}] mark={} delta={} vega={} iv={}",
+                market_index,
+                mark,
+                delta,
+                Decimal::from({ strike_greeks.vega }),
+                Decimal::from({ strike_greeks.volatility })
+            );
+        }
+    }
+}
+
+fn print_margin_account(margin_account: &MarginAccount) {
+    println!("authority: {}", { margin_account.authority });
+    println!("asset: {:?}", { margin_account.asset });
+    println!("account type: {:?}", { margin_account.account_type });
+    println!(
+        "balance: {}",
+        fixed({ margin_account.balance }, PLATFORM_PRECISION)
+    );
+    println!(
+        "rebalance amount: {}",
+        fixed({ margin_account.rebalance_amount }, PLATFORM_PRECISION)
+    );
+    println!("force cancel flag: {}", { margin_account.force_cancel_flag });
+    println!("series expiry: {:?}", { margin_account.series_expiry });
+
+    println!("product ledgers:");
+    for (i, ledger) in margin_account.product_ledgers.iter().enumerate() {
+        if ledger.empty() {
+            continue;
+        }
+        let position = ledger.position;
+        let order_state = ledger.order_state;
+        let opening_orders = { order_state.opening_orders };
+        println!(
+            "  [{}] size={} cost_of_trades={} opening_orders(bid/ask)={}/{} closing_orders={}",
+            i,
+            fixed({ position.size }, POSITION_PRECISION),
+            fixed({ position.cost_of_trades }, PLATFORM_PRECISION),
+            fixed(opening_orders[BID_ORDERS_INDEX], POSITION_PRECISION),
+            fixed(opening_orders[ASK_ORDERS_INDEX], POSITION_PRECISION),
+            fixed({ order_state.closing_orders }, POSITION_PRECISION)
+        );
+    }
+}
+
+fn print_spread_account(spread_account: &SpreadAccount) {
+    println!("authority: {}", { spread_account.authority });
+    println!("asset: {:?}", { spread_account.asset });
+    println!(
+        "balance: {}",
+        fixed({ spread_account.balance }, PLATFORM_PRECISION)
+    );
+    println!("series expiry: {:?}", { spread_account.series_expiry });
+
+    println!("positions:");
+    for (i, position) in spread_account.positions.iter().enumerate() {
+        if position.empty() {
+            continue;
+        }
+        println!(
+            "  [{}] size={} cost_of_trades={}",
+            i,
+            fixed({ position.size }, POSITION_PRECISION),
+            fixed({ position.cost_of_trades }, PLATFORM_PRECISION)
+        );
+    }
+}
+
+fn print_oracle(price: &Price) {
+    let status = match price.agg.status {
+        status if status == PriceStatus::Unknown as u32 => "Unknown",
+        status if status == PriceStatus::Trading as u32 => "Trading",
+        status if status == PriceStatus::Halted as u32 => "Halted",
+        status if status == PriceStatus::Auction as u32 => "Auction",
+        _ => "Invalid",
+    };
+    let scale = (-price.expo).max(0) as u32;
+    println!("magic: {:#x}, version: {}", price.magic, price.ver);
+    println!("exponent: {}", price.expo);
+    println!("price: {}", fixed(price.agg.price, scale));
+    println!("confidence: {}", fixed(price.agg.conf, scale));
+    println!("twap: {}", fixed(price.twap, scale));
+    println!("status: {}", status);
+    println!(
+        "slots: current={} valid={} published={}",
+        price.curr_slot, price.valid_slot, price.agg.pub_slot
+    );
+}
+
+/// Returns the first account of type `T` in `accounts`.
+fn find_account<T: ZetaAccountType>(
+    accounts: &[(String, AccountSnapshot, ZetaAccount)],
+) -> Option<&T> {
+    accounts
+        .iter()
+        .find_map(|(_, _, account)| T::from_account(account))
+}
+
+/// Returns the validated spot price (6.dp) of the oracle dump in `accounts`.
+/// A dump carries no cluster slot, so staleness is measured against the slot
+/// the price account was accumulating when it was dumped.
+fn find_spot_price(accounts: &[(String, AccountSnapshot, ZetaAccount)]) -> Option<u64> {
+    let price = find_account::<Price>(accounts)?;
+
+    let config = OracleConfig::default();
+    match price.get_price(&config, price.curr_slot, PLATFORM_PRECISION) {
+        Ok(oracle_price) => Some(oracle_price.price),
+        Err(err) => {
+            eprintln!("warning: oracle price: {}", err);
+            None
+        }
+    }
+}
+
+/// Prints the `MarginAccountState` of every margin account in `accounts`,
+/// provided a zeta group, greeks and oracle dump were supplied too.
+pub fn print_margin_account_states(accounts: &[(String, AccountSnapshot, ZetaAccount)]) {
+    let zeta_group = find_account::<ZetaGroup>(accounts);
+    let greeks = find_account::<Greeks>(accounts);
+    let spot_price = find_spot_price(accounts);
+
+    let (zeta_group, greeks, spot_price) = match (zeta_group, greeks, spot_price) {
+        (Some(zeta_group), Some(greeks), Some(spot_price)) => (zeta_group, greeks, spot_price),
+        _ => return,
+    };
+    // Only market maker accounts need the concession from the zeta state.
+    let margin_concession_percentage =
+        find_account::<State>(accounts).map(|state| state.margin_concession_percentage);
+    let now_ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    for (path, _, account) in accounts {
+        let margin_account = match account {
+            ZetaAccount::MarginAccount(margin_account) => margin_account,
+            _ => continue,
+        };
+        let (account_asset, group_asset) = (margin_account.asset, zeta_group.asset);
+        if account_asset != group_asset {
+            eprintln!(
+                "warning: {} is a {:?} margin account but the zeta group is {:?}",
+                path, account_asset, group_asset
+            );
+        }
+
+        let state = match get_margin_account_state(
+            zeta_group,
+            margin_account,
+            greeks,
+            NativePrice(spot_price),
+            margin_concession_percentage,
+        ) {
+            Ok(state) => state,
+            Err(err) => {
+                eprintln!("warning: {}: margin account state: {}", path, err);
+                continue;
+            }
+        };
+
+        println!("== {} (MarginAccountState)", path);
+        println!(
+            "balance: {}",
+            fixed(state.balance.get(), PLATFORM_PRECISION)
+        );
+        println!(
+            "initial margin: {}",
+            fixed(state.initial_margin.get(), PLATFORM_PRECISION)
+        );
+        println!(
+            "maintenance margin: {}",
+            fixed(state.maintenance_margin.get(), PLATFORM_PRECISION)
+        );
+        println!(
+            "unrealized pnl: {}",
+            fixed(state.unrealized_pnl, PLATFORM_PRECISION)
+        );
+        println!(
+            "available balance (initial): {}",
+            fixed(state.available_balance_initial, PLATFORM_PRECISION)
+        );
+        println!(
+            "available balance (maintenance): {}",
+            fixed(state.available_balance_maintenance, PLATFORM_PRECISION)
+        );
+        println!(
+            "account equity: {}",
+            fixed(state.account_equity, PLATFORM_PRECISION)
+        );
+
+        for mode in [MarkPriceMode::Fixed, MarkPriceMode::Reprice] {
+            match calculate_liquidation_prices(
+                zeta_group,
+                margin_account,
+                greeks,
+                NativePrice(spot_price),
+                mode,
+                now_ts,
+            ) {
+                Ok(prices) => println!(
+                    "liquidation prices ({:?} marks): lower {}, upper {}",
+                    mode,
+                    liquidation_price(prices.lower),
+                    liquidation_price(prices.upper)
+                ),
+                Err(err) => eprintln!("warning: {}: liquidation prices: {}", path, err),
+            }
+        }
+        println!();
+    }
+}
+
+fn liquidation_price(price: Option<NativePrice>) -> String {
+    match price {
+        Some(price) => fixed(price.get(), PLATFORM_PRECISION).to_string(),
+        None => "none".to_string(),
+    }
+}
+
+pub fn print_spread_account_states(accounts: &[(String, AccountSnapshot, ZetaAccount)]) {
+    let zeta_group = find_account::<ZetaGroup>(accounts);
+    let greeks = find_account::<Greeks>(accounts);
+
+    let (zeta_group, greeks) = match (zeta_group, greeks) {
+        (Some(zeta_group), Some(greeks)) => (zeta_group, greeks),
+        _ => return,
+    };
+
+    for (path, _, account) in accounts {
+        let spread_account = match account {
+            ZetaAccount::SpreadAccount(spread_account) => spread_account,
+            _ => continue,
+        };
+
+        let state = match calculate_spread_account_state(zeta_group, spread_account, greeks) {
+            Ok(state) => state,
+            Err(err) => {
+                eprintln!("warning: {}: spread account state: {}", path, err);
+                continue;
+            }
+        };
+
+        println!("== {} (SpreadAccountState)", path);
+        println!(
+            "balance: {}",
+            fixed(state.balance.get(), PLATFORM_PRECISION)
+        );
+        println!("margin: {}", fixed(state.margin.get(), PLATFORM_PRECISION));
+        println!(
+            "unrealized pnl: {}",
+            fixed(state.unrealized_pnl, PLATFORM_PRECISION)
+        );
+        println!(
+            "excess balance: {}",
+            fixed(state.excess_balance.get(), PLATFORM_PRECISION)
+        );
+        println!(
+            "account equity: {}",
+            fixed(state.account_equity, PLATFORM_PRECISION)
+        );
+        println!();
+    }
+}
+
+/// Prints the futures basis and implied rates of each active expiry,
+/// provided a zeta group, greeks and oracle dump were supplied.
+pub fn print_futures_bases(accounts: &[(String, AccountSnapshot, ZetaAccount)]) {
+    let zeta_group = find_account::<ZetaGroup>(accounts);
+    let greeks = find_account::<Greeks>(accounts);
+    let spot_price = find_spot_price(accounts);
+
+    let (zeta_group, greeks, spot_price) = match (zeta_group, greeks, spot_price) {
+        (Some(zeta_group), Some(greeks), Some(spot_price)) => (zeta_group, greeks, spot_price),
+        _ => return,
+    };
+    let now_ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    println!("== futures basis");
+    for expiry_index in 0..ACTIVE_EXPIRIES {
+        let basis = match calculate_futures_basis(
+            zeta_group,
+            greeks,
+            NativePrice(spot_price),
+            expiry_index,
+            now_ts,
+        ) {
+            Ok(basis) => basis,
+            Err(err) => {
+                eprintln!("warning: expiry {}: futures basis: {}", expiry_index, err);
+                continue;
+            }
+        };
+
+        println!(
+            "expiry {} ({}): spot={} future={} basis={}",
+            expiry_index,
+            basis.expiry_ts,
+            fixed(basis.spot_price.get(), PLATFORM_PRECISION),
+            fixed(basis.futures_price.get(), PLATFORM_PRECISION),
+            fixed(basis.basis, PLATFORM_PRECISION)
+        );
+        println!(
+            "  implied rate={} greeks rate={} difference={}",
+            fixed(basis.implied_interest_rate, PRICING_PRECISION),
+            fixed(basis.interest_rate, PRICING_PRECISION),
+            fixed(basis.interest_rate_difference, PRICING_PRECISION)
+        );
+        for forward in basis.parity_forwards {
+            println!(
+                "  strike={} parity forward={} implied rate={}",
+                fixed(forward.strike.get(), PLATFORM_PRECISION),
+                fixed(forward.forward_price.get(), PLATFORM_PRECISION),
+                fixed(forward.implied_interest_rate, PRICING_PRECISION)
+            );
+        }
+    }
+    println!();
+}
@@ -0,0 +1,59 @@
+//! Offline account inspector for Zeta account dumps.
+//!
+//! Reads account snapshots from disk, detects the layout by discriminator and
+//! pretty-prints the decoded account. No RPC connection is required.
+
+mod decode;
+mod display;
+mod snapshot;
+
+use crate::decode::*;
+use crate::snapshot::*;
+use std::process;
+
+const USAGE: &str = "Usage: fuze-inspect <FILE>...
+
+Decodes each Zeta account dump and prints its contents. A FILE may hold the
+JSON written by `solana account --output json`, a base64 string or raw account
+bytes. Supported layouts: State, ZetaGroup, Greeks, MarginAccount, SpreadAccount
+and Pyth oracle accounts.
+
+If a margin account is given together with a zeta group, greeks and oracle
+dump, its MarginAccountState and liquidation prices are printed as well.
+Market maker accounts also need the State dump for their margin concession.
+Likewise a spread account given with a zeta group and greeks dump has its
+SpreadAccountState printed. A zeta group, greeks and oracle dump together also
+print the futures basis and the rates implied by the futures and put-call
+parity next to the greeks interest rate.";
+
+fn main() {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+    if paths.iter().any(|path| path == "-h" || path == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    if let Err(err) = run(paths) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(paths: Vec<String>) -> Result<(), String> {
+    let mut accounts = Vec::with_capacity(paths.len());
+    for path in paths {
+        let snapshot = AccountSnapshot::load(&path)?;
+        let account = ZetaAccount::decode(&snapshot.data).map_err(|e| format!("{}: {}", path, e))?;
+        display::print_account(&path, &snapshot, &account);
+        accounts.push((path, snapshot, account));
+    }
+
+    display::print_margin_account_states(&accounts);
+    display::print_spread_account_states(&accounts);
+    display::print_futures_bases(&accounts);
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use anchor_lang::prelude::*;
+use serde_json::Value;
+use std::fs;
+use std::str::FromStr;
+
+/// An account read from disk.
+///
+/// Supports the JSON written by `solana account --output json`, a bare base64
+/// string and the raw bytes written by `solana account --output-file`.
+pub struct AccountSnapshot {
+    pub pubkey: Option<Pubkey>,
+    pub owner: Option<Pubkey>,
+    pub data: Vec<u8>,
+}
+
+impl AccountSnapshot {
+    pub fn load(path: &str) -> std::result::Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(bytes: &[u8]) -> std::result::Result<Self, String> {
+        if let Ok(json) = serde_json::from_slice::<Value>(bytes) {
+            return Self::from_json(&json);
+        }
+
+        // Anything that isn't json or valid base64 text is treated as a raw dump.
+        let data = match std::str::from_utf8(bytes) {
+            Ok(text) => base64::decode(text.trim()).unwrap_or_else(|_| bytes.to_vec()),
+            Err(_) => bytes.to_vec(),
+        };
+
+        Ok(AccountSnapshot {
+            pubkey: None,
+            owner: None,
+            data,
+        })
+    }
+
+    fn from_json(json: &Value) -> std::result::Result<Self, String> {
+        let account = json
+            .get("account")
+            .ok_or_else(|| "missing `account` field".to_string())?;
+
+        let data = match &account["data"] {
+            Value::Array(parts) => {
+                let encoded = parts
+                    .get(0)
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| "missing account data".to_string())?;
+                let encoding = parts.get(1).and_then(Value::as_str).unwrap_or("base64");
+                if encoding != "base64" {
+                    return Err(format!("unsupported data encoding `{}`", encoding));
+                }
+                base64::decode(encoded).map_err(|e| format!("invalid base64 data: {}", e))?
+            }
+            Value::String(encoded) => {
+                base64::decode(encoded).map_err(|e| format!("invalid base64 data: {}", e))?
+            }
+            _ => return Err("missing account data".to_string()),
+        };
+
+        Ok(AccountSnapshot {
+            pubkey: parse_pubkey(json.get("pubkey"))?,
+            owner: parse_pubkey(account.get("owner"))?,
+            data,
+        })
+    }
+}
+
+fn parse_pubkey(value: Option<&Value>) -> std::result::Result<Option<Pubkey>, String> {
+    match value.and_then(Value::as_str) {
+        Some(key) => Pubkey::from_str(key)
+            .map(Some)
+            .map_err(|e| format!("invalid pubkey `{}`: {}", key, e)),
+        None => Ok(None),
+    }
+}
@@ -7,7 +7,6 @@ use std::ops::Deref;
 pub mod address;
 pub mod constants;
 pub mod context;
-pub mod pyth_client;
 pub mod zeta_account;
 pub mod zeta_client;
 pub mod zeta_constants;
@@ -368,6 +367,12 @@ pub enum ErrorCode {
     InvalidUserUsdcAccountOwner,
     #[msg("Invalid vault admin")]
     InvalidVaultAdmin,
+    #[msg("Math operation overflowed")]
+    MathOverflow,
+    #[msg("Math operation divided by zero")]
+    DivisionByZero,
+    #[msg("Invalid side, must be Bid or Ask")]
+    InvalidSide,
 }
 
 // Access control modifiers.
@@ -194,33 +194,6 @@ pub fn get_maintenance_margin_per_lot(
     Ok(u64::try_from(maintenance_margin).unwrap())
 }
 
-/// Returns the native oracle price (6.dp)
-///
-/// # Arguments
-///
-/// * `oracle` - Oracle account.
-pub fn get_native_oracle_price(oracle: &AccountInfo) -> u64 {
-    let oracle_price = pyth_client::Price::load(&oracle).unwrap();
-    (oracle_price.agg.price as u128)
-        .checked_mul(10u128.pow(PLATFORM_PRECISION.into()))
-        .unwrap()
-        .checked_div(10u128.pow((-oracle_price.expo).try_into().unwrap()))
-        .unwrap()
-        .try_into()
-        .unwrap()
-}
-
-pub fn get_oracle_price(oracle: &AccountInfo, precision: u32) -> i128 {
-    let oracle_price = pyth_client::Price::load(&oracle).unwrap();
-    (oracle_price.agg.price as u128)
-        .checked_mul(10u128.pow(precision))
-        .unwrap()
-        .checked_div(10u128.pow((-oracle_price.expo).try_into().unwrap()))
-        .unwrap()
-        .try_into()
-        .unwrap()
-}
-
 /// Returns the market index given an expiry index and index into the slice.
 ///
 /// # Arguments
@@ -234,3 +207,75 @@ pub fn get_products_slice_market_index(expiry_index: usize, product_index: usize
         .checked_add(product_index)
         .unwrap()
 }
+
+/// Taker fee (6.dp) of trading `size` lots of an option, charged on the spot
+/// notional at Zeta's `State.native_underlying_fee_percentage`.
+pub fn get_option_taker_fee(
+    spot: u64,
+    size: u64,
+    native_underlying_fee_percentage: u64,
+) -> Result<u64> {
+    (spot as u128)
+        .checked_mul(size as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(POSITION_PRECISION_DENOMINATOR)
+        .ok_or(ErrorCode::DivisionByZero)?
+        .checked_mul(native_underlying_fee_percentage as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(NATIVE_PRECISION_DENOMINATOR)
+        .ok_or(ErrorCode::DivisionByZero)?
+        .try_into()
+        .map_err(|_| ProgramError::from(ErrorCode::MathOverflow))
+}
+
+/// Premium of an order net of its fee (6.dp). Positive when premium is
+/// received (asks) and negative when it is paid (bids).
+/// Auction prices should clear the vault's target premium after fees.
+pub fn get_net_premium(price: u64, size: u64, side: Side, fee: u64) -> Result<i64> {
+    let premium: i64 = (price as u128)
+        .checked_mul(size as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(POSITION_PRECISION_DENOMINATOR)
+        .ok_or(ErrorCode::DivisionByZero)?
+        .try_into()
+        .map_err(|_| ProgramError::from(ErrorCode::MathOverflow))?;
+    let fee = i64::try_from(fee).map_err(|_| ProgramError::from(ErrorCode::MathOverflow))?;
+
+    match side {
+        Side::Bid => Ok(-premium.checked_add(fee).ok_or(ErrorCode::MathOverflow)?),
+        Side::Ask => Ok(premium.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?),
+        Side::Uninitialized => wrap_error!(Err(ErrorCode::InvalidSide.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_option_taker_fee() {
+        // 0.03% of 10 lots at a spot of 100.
+        assert_eq!(
+            get_option_taker_fee(100_000_000, 10_000, 30_000).unwrap(),
+            300_000
+        );
+        assert_eq!(get_option_taker_fee(100_000_000, 10_000, 0).unwrap(), 0);
+        assert!(get_option_taker_fee(u64::MAX, u64::MAX, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_get_net_premium() {
+        // 10 lots at 2 with a fee of 0.3.
+        assert_eq!(
+            get_net_premium(2_000_000, 10_000, Side::Bid, 300_000).unwrap(),
+            -20_300_000
+        );
+        assert_eq!(
+            get_net_premium(2_000_000, 10_000, Side::Ask, 300_000).unwrap(),
+            19_700_000
+        );
+        assert!(get_net_premium(2_000_000, 10_000, Side::Uninitialized, 300_000).is_err());
+        assert!(get_net_premium(u64::MAX, u64::MAX, Side::Ask, 0).is_err());
+        assert!(get_net_premium(2_000_000, 10_000, Side::Ask, u64::MAX).is_err());
+    }
+}
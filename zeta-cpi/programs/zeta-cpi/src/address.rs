@@ -0,0 +1,22 @@
+// Owners of the oracle accounts accepted by `oracle::load_oracle_source`.
+
+pub mod pyth_oracle {
+    use anchor_lang::declare_id;
+    #[cfg(feature = "devnet")]
+    declare_id!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
+    #[cfg(not(feature = "devnet"))]
+    declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+
+pub mod pyth_receiver {
+    use anchor_lang::declare_id;
+    declare_id!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+}
+
+pub mod switchboard_v2 {
+    use anchor_lang::declare_id;
+    #[cfg(feature = "devnet")]
+    declare_id!("2TfB33aLaneQb5TNVwyDz3jSZXS6jdW2ARw1Dgf84XCG");
+    #[cfg(not(feature = "devnet"))]
+    declare_id!("SW1TCH7qEPTdLsDHRgPuMQjbQxKdH2aBStViMFnt64f");
+}
@@ -0,0 +1,50 @@
+// Constants of the zeta-cpi program.
+// Zeta's constants live in `zeta_constants`.
+
+pub const TRADE_LEDGER_SEED: &str = "trade-ledger";
+
+// Size of the trade ledger's fill history, older fills are overwritten.
+pub const TRADE_LEDGER_FILLS: usize = 64;
+
+pub const ORACLE_EMA_SEED: &str = "oracle-ema";
+
+// Decimals of a `NATIVE_PRECISION_DENOMINATOR` percentage, see `units::Pct`.
+pub const PCT_PRECISION: u32 = 8;
+
+// Pyth price account header.
+pub const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+pub const PYTH_VERSION: u32 = 2;
+pub const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+
+// Anchor discriminators of the Pyth receiver and Switchboard V2 accounts.
+pub const PYTH_PRICE_UPDATE_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+pub const SWITCHBOARD_AGGREGATOR_DISCRIMINATOR: [u8; 8] = [217, 230, 65, 101, 201, 162, 27, 125];
+
+// Serum DEX accounts start with this padding, markets carry the Initialized
+// and Market account flags.
+pub const SERUM_ACCOUNT_HEAD_PADDING: &[u8; 5] = b"serum";
+pub const SERUM_MARKET_ACCOUNT_FLAGS: u64 = 0b11;
+
+// Default oracle validation, see `oracle::OracleConfig`.
+pub const DEFAULT_ORACLE_MAX_STALENESS_SLOTS: u64 = 25;
+pub const DEFAULT_ORACLE_MAX_CONFIDENCE_PERCENTAGE: u64 = 2_000_000; // 2%
+
+// Staleness accepted for an `OracleEma`, measured from its last crank. The
+// average moves slowly, so it stays usable far longer than a spot print.
+pub const ORACLE_EMA_MAX_STALENESS_SLOTS: u64 = 1_500; // ~10 minutes
+
+// Accounts per market group in the remaining accounts of `place_orders`.
+pub const PLACE_ORDERS_MARKET_ACCOUNTS: usize = 13;
+
+// Compute estimates bounding how many orders `place_orders` attempts. They are
+// static upper estimates, the runtime exposes no remaining compute to check:
+// the base covers loading the zeta group, greeks, margin account and oracle
+// and the margin pre-check, which walks every product ledger. Each order CPIs
+// into Zeta's `place_order_v3`, which runs its own margin check over every
+// product ledger before the Serum `new_order` matches against resting orders,
+// and then updates the trade ledger. Overestimating only places fewer orders,
+// underestimating fails the whole transaction, so both round up.
+// The default Solana limit fits 3 orders, the 1.4M maximum fits 27.
+pub const DEFAULT_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+pub const PLACE_ORDERS_BASE_COMPUTE_UNITS: u32 = 40_000;
+pub const PLACE_ORDER_COMPUTE_UNITS: u32 = 50_000;
@@ -1,5 +1,6 @@
 use crate::zeta_context::*;
 use crate::*;
+use anchor_spl::token::Token;
 
 // CPI Program Context
 // Edit this as you wish for your own program instructions
@@ -28,24 +29,153 @@ pub struct WithdrawCaller<'info> {
     pub withdraw_cpi_accounts: Withdraw<'info>,
 }
 
+#[derive(Accounts)]
+pub struct InitializeInsuranceDepositAccountCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub initialize_insurance_deposit_cpi_accounts: InitializeInsuranceDepositAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DepositInsuranceVaultCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub deposit_insurance_vault_cpi_accounts: DepositInsuranceVault<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawInsuranceVaultCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub withdraw_insurance_vault_cpi_accounts: WithdrawInsuranceVault<'info>,
+}
+
 #[derive(Accounts)]
 pub struct InitializeOpenOrdersCaller<'info> {
     pub zeta_program: AccountInfo<'info>,
     pub initialize_open_orders_cpi_accounts: InitializeOpenOrders<'info>,
 }
 
+#[derive(Accounts)]
+pub struct CloseMarginAccountCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub close_margin_account_cpi_accounts: CloseMarginAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSpreadAccountCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub close_spread_account_cpi_accounts: CloseSpreadAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseOpenOrdersCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub close_open_orders_cpi_accounts: CloseOpenOrders<'info>,
+}
+
+// The margin account's trade ledger may be passed as a remaining account to
+// record taker fills, see `place_and_record_order`.
 #[derive(Accounts)]
 pub struct PlaceOrderCaller<'info> {
     pub zeta_program: AccountInfo<'info>,
     pub place_order_cpi_accounts: PlaceOrder<'info>,
 }
 
+#[derive(Accounts)]
+pub struct InitializeTradeLedger<'info> {
+    #[account(
+        init,
+        seeds = [TRADE_LEDGER_SEED.as_bytes(), margin_account.key().as_ref()],
+        bump,
+        payer = authority,
+        space = DISCRIMINATOR_SIZE + std::mem::size_of::<TradeLedger>()
+    )]
+    pub trade_ledger: AccountLoader<'info, TradeLedger>,
+    pub zeta_group: AccountInfo<'info>,
+    pub margin_account: AccountInfo<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(window_seconds: u64)]
+pub struct InitializeOracleEma<'info> {
+    #[account(
+        init,
+        seeds = [
+            ORACLE_EMA_SEED.as_bytes(),
+            oracle.key().as_ref(),
+            &window_seconds.to_le_bytes()
+        ],
+        bump,
+        payer = payer,
+        space = DISCRIMINATOR_SIZE + std::mem::size_of::<OracleEma>()
+    )]
+    pub oracle_ema: AccountLoader<'info, OracleEma>,
+    pub zeta_group: AccountInfo<'info>,
+    pub oracle: AccountInfo<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateOracleEma<'info> {
+    #[account(
+        mut,
+        seeds = [
+            ORACLE_EMA_SEED.as_bytes(),
+            oracle.key().as_ref(),
+            &oracle_ema.load()?.window_seconds.to_le_bytes()
+        ],
+        bump = oracle_ema.load()?.nonce
+    )]
+    pub oracle_ema: AccountLoader<'info, OracleEma>,
+    pub oracle: AccountInfo<'info>,
+}
+
+// Shared accounts of a batch of orders. Every market traded takes a group of
+// `PLACE_ORDERS_MARKET_ACCOUNTS` remaining accounts: open_orders, market,
+// request_queue, event_queue, bids, asks, order_payer_token_account,
+// coin_vault, pc_vault, coin_wallet, pc_wallet, market_node and market_mint.
+// The trade ledger may follow the last group.
+#[derive(Accounts)]
+pub struct PlaceOrdersCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub state: AccountInfo<'info>,
+    pub zeta_group: AccountInfo<'info>,
+    #[account(mut)]
+    pub margin_account: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    pub dex_program: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    pub serum_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub greeks: AccountInfo<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    pub oracle: AccountInfo<'info>,
+    pub mint_authority: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 pub struct CancelOrderCaller<'info> {
     pub zeta_program: AccountInfo<'info>,
     pub cancel_order_cpi_accounts: CancelOrder<'info>,
 }
 
+#[derive(Accounts)]
+pub struct ForceCancelOrdersCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub force_cancel_orders_cpi_accounts: ForceCancelOrders<'info>,
+}
+
+// The trade ledger may be passed as a remaining account, as for `PlaceOrderCaller`.
+#[derive(Accounts)]
+pub struct CancelReplaceCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub cancel_order_cpi_accounts: CancelOrder<'info>,
+    pub place_order_cpi_accounts: PlaceOrder<'info>,
+}
+
 #[derive(Accounts)]
 pub struct PositionMovementCaller<'info> {
     pub zeta_program: AccountInfo<'info>,
@@ -58,6 +188,28 @@ pub struct TransferExcessSpreadBalanceCaller<'info> {
     pub transfer_excess_spread_balance_cpi_accounts: TransferExcessSpreadBalance<'info>,
 }
 
+#[derive(Accounts)]
+pub struct LiquidateCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub liquidate_cpi_accounts: Liquidate<'info>,
+}
+
+// Open orders accounts are passed as remaining accounts.
+#[derive(Accounts)]
+pub struct CrankEventQueueCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub crank_event_queue_cpi_accounts: CrankEventQueue<'info>,
+}
+
+// Open orders accounts are passed as remaining accounts.
+#[derive(Accounts)]
+pub struct SettleDexFundsCaller<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub settle_dex_funds_cpi_accounts: SettleDexFunds<'info>,
+}
+
+// The `OracleEma` of the oracle may be passed as a remaining account to serve
+// as the reference price, see `load_reference_source`.
 #[derive(Accounts)]
 pub struct ReadProgramData<'info> {
     pub state: AccountInfo<'info>,
@@ -67,6 +219,55 @@ pub struct ReadProgramData<'info> {
     pub oracle: AccountInfo<'info>,
 }
 
+// Takes the same optional `OracleEma` remaining account as `ReadProgramData`.
+#[derive(Accounts)]
+pub struct SimulateOrder<'info> {
+    pub state: AccountInfo<'info>,
+    pub zeta_group: AccountInfo<'info>,
+    pub margin_account: AccountInfo<'info>,
+    pub greeks: AccountInfo<'info>,
+    pub oracle: AccountInfo<'info>,
+}
+
+// Margin accounts to scan are passed as remaining accounts.
+#[derive(Accounts)]
+pub struct FindForceCancellableAccounts<'info> {
+    pub zeta_program: AccountInfo<'info>,
+    pub zeta_group: AccountInfo<'info>,
+    pub greeks: AccountInfo<'info>,
+    pub oracle: AccountInfo<'info>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct OrderSpec {
+    pub market_index: u8,
+    pub price: u64,
+    pub size: u64,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub client_order_id: Option<u64>,
+}
+
+impl OrderSpec {
+    pub fn new(
+        market_index: u8,
+        price: NativePrice,
+        size: Lots,
+        side: Side,
+        order_type: OrderType,
+        client_order_id: Option<u64>,
+    ) -> Self {
+        OrderSpec {
+            market_index,
+            price: price.get(),
+            size: size.get(),
+            side,
+            order_type,
+            client_order_id,
+        }
+    }
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct PositionMovementArg {
     pub index: u8,
@@ -1,18 +1,42 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::set_return_data;
 use rust_decimal::prelude::*;
 
+pub mod address;
+pub mod constants;
 pub mod context;
+pub mod oracle;
+pub mod oracle_ema;
 pub mod pyth_client;
+pub mod pyth_price_update;
+pub mod serum_market;
+pub mod switchboard_client;
+pub mod trade_ledger;
+pub mod units;
 pub mod zeta_account;
+pub mod zeta_basis;
 pub mod zeta_calculations;
 pub mod zeta_client;
 pub mod zeta_constants;
 pub mod zeta_context;
+pub mod zeta_fees;
+pub mod zeta_pricing;
+pub mod zeta_risk;
 pub mod zeta_utils;
+use crate::constants::*;
 use crate::context::*;
+use crate::oracle::*;
+use crate::oracle_ema::*;
+use crate::serum_market::*;
+use crate::trade_ledger::*;
+use crate::units::*;
 use crate::zeta_account::*;
+use crate::zeta_basis::*;
 use crate::zeta_calculations::*;
 use crate::zeta_constants::*;
+use crate::zeta_fees::*;
+use crate::zeta_pricing::*;
+use crate::zeta_risk::*;
 use crate::zeta_utils::*;
 
 #[cfg(feature = "devnet")]
@@ -59,6 +83,44 @@ pub mod zeta_cpi {
         )
     }
 
+    pub fn initialize_insurance_deposit_account(
+        ctx: Context<InitializeInsuranceDepositAccountCaller>,
+        nonce: u8,
+    ) -> Result<()> {
+        zeta_client::initialize_insurance_deposit_account(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts
+                .initialize_insurance_deposit_cpi_accounts
+                .clone(),
+            None,
+            nonce,
+        )
+    }
+
+    pub fn deposit_insurance_vault(
+        ctx: Context<DepositInsuranceVaultCaller>,
+        amount: u64,
+    ) -> Result<()> {
+        zeta_client::deposit_insurance_vault(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.deposit_insurance_vault_cpi_accounts.clone(),
+            None,
+            amount,
+        )
+    }
+
+    pub fn withdraw_insurance_vault(
+        ctx: Context<WithdrawInsuranceVaultCaller>,
+        percentage_amount: u64,
+    ) -> Result<()> {
+        zeta_client::withdraw_insurance_vault(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.withdraw_insurance_vault_cpi_accounts.clone(),
+            None,
+            percentage_amount,
+        )
+    }
+
     pub fn initialize_open_orders(ctx: Context<InitializeOpenOrdersCaller>) -> Result<()> {
         zeta_client::initialize_open_orders(
             ctx.accounts.zeta_program.clone(),
@@ -67,26 +129,153 @@ pub mod zeta_cpi {
         )
     }
 
-    pub fn place_order(
-        ctx: Context<PlaceOrderCaller>,
+    /// Closes an empty margin account (no positions, orders or balance),
+    /// returning its rent to the authority.
+    pub fn close_margin_account(ctx: Context<CloseMarginAccountCaller>) -> Result<()> {
+        {
+            let accounts = &ctx.accounts.close_margin_account_cpi_accounts;
+            let margin_account =
+                deserialize_account_info_zerocopy::<MarginAccount>(&accounts.margin_account)?;
+            if !margin_account.empty() {
+                return wrap_error!(Err(error!(FuzeErrorCode::AccountNotEmpty)));
+            }
+        }
+
+        zeta_client::close_margin_account(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.close_margin_account_cpi_accounts.clone(),
+            None,
+        )
+    }
+
+    /// Closes an empty spread account (no positions or balance), returning
+    /// its rent to the authority.
+    pub fn close_spread_account(ctx: Context<CloseSpreadAccountCaller>) -> Result<()> {
+        {
+            let accounts = &ctx.accounts.close_spread_account_cpi_accounts;
+            let spread_account =
+                deserialize_account_info_zerocopy::<SpreadAccount>(&accounts.spread_account)?;
+            if !spread_account.empty() {
+                return wrap_error!(Err(error!(FuzeErrorCode::AccountNotEmpty)));
+            }
+        }
+
+        zeta_client::close_spread_account(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.close_spread_account_cpi_accounts.clone(),
+            None,
+        )
+    }
+
+    /// Closes the margin account's open orders for `market`, which must not
+    /// have active orders left.
+    pub fn close_open_orders(ctx: Context<CloseOpenOrdersCaller>, map_nonce: u8) -> Result<()> {
+        {
+            let accounts = &ctx.accounts.close_open_orders_cpi_accounts;
+            let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&accounts.zeta_group)?;
+            let market_index = zeta_group.get_product_index_by_key(&accounts.market.key())?;
+            if market_index >= ACTIVE_MARKETS {
+                return wrap_error!(Err(error!(FuzeErrorCode::InvalidMarketIndex)));
+            }
+
+            let margin_account =
+                deserialize_account_info_zerocopy::<MarginAccount>(&accounts.margin_account)?;
+            if margin_account.product_ledgers[market_index]
+                .order_state
+                .has_active_orders()
+            {
+                return wrap_error!(Err(error!(FuzeErrorCode::AccountHasActiveOrders)));
+            }
+        }
+
+        zeta_client::close_open_orders(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.close_open_orders_cpi_accounts.clone(),
+            None,
+            map_nonce,
+        )
+    }
+
+    pub fn initialize_trade_ledger(ctx: Context<InitializeTradeLedger>) -> Result<()> {
+        let margin_account =
+            deserialize_account_info_zerocopy::<MarginAccount>(&ctx.accounts.margin_account)?;
+        if margin_account.authority != ctx.accounts.authority.key() {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidTradeLedger)));
+        }
+        let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&ctx.accounts.zeta_group)?;
+
+        let mut trade_ledger = ctx.accounts.trade_ledger.load_init()?;
+        trade_ledger.nonce = *ctx.bumps.get("trade_ledger").unwrap();
+        trade_ledger.authority = ctx.accounts.authority.key();
+        trade_ledger.margin_account = ctx.accounts.margin_account.key();
+        trade_ledger.roll_expiries(&zeta_group);
+        Ok(())
+    }
+
+    pub fn initialize_oracle_ema(
+        ctx: Context<InitializeOracleEma>,
+        window_seconds: u64,
+    ) -> Result<()> {
+        let spot_price = {
+            let zeta_group =
+                deserialize_account_info_zerocopy::<ZetaGroup>(&ctx.accounts.zeta_group)?;
+            get_native_oracle_price(
+                load_zeta_group_oracle_source(&zeta_group, &ctx.accounts.oracle)?.as_ref(),
+            )?
+        };
+        let clock = Clock::get()?;
+
+        let mut oracle_ema = ctx.accounts.oracle_ema.load_init()?;
+        oracle_ema.nonce = *ctx.bumps.get("oracle_ema").unwrap();
+        oracle_ema.oracle = ctx.accounts.oracle.key();
+        oracle_ema.window_seconds = window_seconds;
+        oracle_ema.update(spot_price.get(), clock.slot, clock.unix_timestamp)
+    }
+
+    /// Permissionless crank moving the EMA towards the oracle's spot price.
+    /// The PDA seeds tie the EMA to the oracle checked against the zeta group
+    /// at initialization.
+    pub fn update_oracle_ema(ctx: Context<UpdateOracleEma>) -> Result<()> {
+        let spot_price =
+            get_native_oracle_price(load_oracle_source(&ctx.accounts.oracle)?.as_ref())?;
+        let clock = Clock::get()?;
+
+        let mut oracle_ema = ctx.accounts.oracle_ema.load_mut()?;
+        oracle_ema.update(spot_price.get(), clock.slot, clock.unix_timestamp)
+    }
+
+    pub fn place_order<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, PlaceOrderCaller<'info>>,
         price: u64,
         size: u64,
         side: Side,
         client_order_id: Option<u64>,
     ) -> Result<()> {
-        zeta_client::place_order(
-            ctx.accounts.zeta_program.clone(),
-            ctx.accounts.place_order_cpi_accounts.clone(),
-            None,
+        let (price, size) = round_order_increments(
+            &ctx.accounts.place_order_cpi_accounts.market_accounts.market,
             price,
             size,
             side,
-            client_order_id,
+        )?;
+        place_and_record_order(
+            &ctx.accounts.place_order_cpi_accounts,
+            ctx.remaining_accounts.first(),
+            || {
+                zeta_client::place_order(
+                    ctx.accounts.zeta_program.clone(),
+                    ctx.accounts.place_order_cpi_accounts.clone(),
+                    None,
+                    price,
+                    size,
+                    side,
+                    client_order_id,
+                )
+            },
         )
     }
 
-    pub fn place_order_v3(
-        ctx: Context<PlaceOrderCaller>,
+    pub fn place_order_v3<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, PlaceOrderCaller<'info>>,
         price: u64,
         size: u64,
         side: Side,
@@ -94,19 +283,175 @@ pub mod zeta_cpi {
         client_order_id: Option<u64>,
         tag: Option<String>,
     ) -> Result<()> {
-        zeta_client::place_order_v3(
-            ctx.accounts.zeta_program.clone(),
-            ctx.accounts.place_order_cpi_accounts.clone(),
-            None,
+        let (price, size) = round_order_increments(
+            &ctx.accounts.place_order_cpi_accounts.market_accounts.market,
             price,
             size,
             side,
-            order_type,
-            client_order_id,
-            tag,
+        )?;
+        place_and_record_order(
+            &ctx.accounts.place_order_cpi_accounts,
+            ctx.remaining_accounts.first(),
+            || {
+                zeta_client::place_order_v3(
+                    ctx.accounts.zeta_program.clone(),
+                    ctx.accounts.place_order_cpi_accounts.clone(),
+                    None,
+                    price,
+                    size,
+                    side,
+                    order_type,
+                    client_order_id,
+                    tag,
+                )
+            },
         )
     }
 
+    /// `place_order_v3` with `ImmediateOrCancel` and expiring orders, a
+    /// `tif_offset` ending after the expiry of the market's series is refused.
+    pub fn place_order_v4<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, PlaceOrderCaller<'info>>,
+        price: u64,
+        size: u64,
+        side: Side,
+        order_type: OrderType,
+        client_order_id: Option<u64>,
+        tag: Option<String>,
+        tif_offset: Option<u16>,
+    ) -> Result<()> {
+        {
+            let accounts = &ctx.accounts.place_order_cpi_accounts;
+            let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&accounts.zeta_group)?;
+            validate_tif_offset(
+                &zeta_group,
+                &accounts.market_accounts.market.key(),
+                tif_offset,
+            )?;
+        }
+
+        let (price, size) = round_order_increments(
+            &ctx.accounts.place_order_cpi_accounts.market_accounts.market,
+            price,
+            size,
+            side,
+        )?;
+        place_and_record_order(
+            &ctx.accounts.place_order_cpi_accounts,
+            ctx.remaining_accounts.first(),
+            || {
+                zeta_client::place_order_v4(
+                    ctx.accounts.zeta_program.clone(),
+                    ctx.accounts.place_order_cpi_accounts.clone(),
+                    None,
+                    price,
+                    size,
+                    side,
+                    order_type,
+                    client_order_id,
+                    tag,
+                    tif_offset,
+                )
+            },
+        )
+    }
+
+    /// Places a batch of orders for one margin account, e.g. a strike ladder.
+    /// Orders past what `compute_unit_limit` (the transaction's requested
+    /// limit, 200k by default) fits by `get_max_batch_orders` are left
+    /// unplaced, the orders attempted have to fit the account's initial margin
+    /// up front, at their prices and sizes rounded onto the market's ticks and
+    /// lots. The number of orders placed is written to the return data.
+    /// The limit is not checked against the transaction's, overstating it
+    /// fails the transaction instead of stopping early.
+    pub fn place_orders<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, PlaceOrdersCaller<'info>>,
+        orders: Vec<OrderSpec>,
+        compute_unit_limit: Option<u32>,
+    ) -> Result<()> {
+        if orders.is_empty() {
+            return wrap_error!(Err(error!(FuzeErrorCode::EmptyOrderBatch)));
+        }
+        let (market_groups, trade_ledger) = split_place_orders_accounts(ctx.remaining_accounts)?;
+        let max_orders =
+            get_max_batch_orders(compute_unit_limit.unwrap_or(DEFAULT_COMPUTE_UNIT_LIMIT));
+        let mut attempted = Vec::with_capacity(orders.len().min(max_orders));
+        let mut attempted_accounts = Vec::with_capacity(attempted.capacity());
+
+        {
+            let zeta_group =
+                deserialize_account_info_zerocopy::<ZetaGroup>(&ctx.accounts.zeta_group)?;
+            for order in orders.iter().take(max_orders) {
+                let market_index = usize::from(order.market_index);
+                if market_index >= ACTIVE_MARKETS {
+                    return wrap_error!(Err(error!(FuzeErrorCode::InvalidMarketIndex)));
+                }
+                let market = zeta_group.products[market_index].market;
+                let place_order_accounts =
+                    get_batch_place_order_accounts(&ctx.accounts, market_groups, &market)?;
+                let (price, size) = round_order_increments(
+                    &place_order_accounts.market_accounts.market,
+                    order.price,
+                    order.size,
+                    order.side,
+                )?;
+                attempted.push(OrderSpec {
+                    price,
+                    size,
+                    ..order.clone()
+                });
+                attempted_accounts.push(place_order_accounts);
+            }
+
+            let greeks = deserialize_account_info_zerocopy::<Greeks>(&ctx.accounts.greeks)?;
+            let margin_account =
+                deserialize_account_info_zerocopy::<MarginAccount>(&ctx.accounts.margin_account)?;
+            let state = deserialize_account_info::<State>(&ctx.accounts.state)?;
+            let spot_price = get_native_oracle_price(
+                load_zeta_group_oracle_source(&zeta_group, &ctx.accounts.oracle)?.as_ref(),
+            )?;
+
+            let margin_account_state = margin_account.simulate_orders(
+                &attempted,
+                &greeks,
+                &zeta_group,
+                spot_price,
+                Some(state.margin_concession_percentage),
+            )?;
+            if margin_account_state.available_balance_initial < 0 {
+                return wrap_error!(Err(error!(FuzeErrorCode::InsufficientMarginForOrders)));
+            }
+        }
+
+        let mut placed: u32 = 0;
+        for (order, place_order_accounts) in attempted.iter().zip(attempted_accounts) {
+            place_and_record_order(&place_order_accounts, trade_ledger, || {
+                zeta_client::place_order_v3(
+                    ctx.accounts.zeta_program.clone(),
+                    place_order_accounts.clone(),
+                    None,
+                    order.price,
+                    order.size,
+                    order.side,
+                    order.order_type,
+                    order.client_order_id,
+                    None,
+                )
+            })?;
+            placed += 1;
+        }
+
+        if (placed as usize) < orders.len() {
+            msg!(
+                "Compute limit reached, placed {} of {} orders",
+                placed,
+                orders.len()
+            );
+        }
+        set_return_data(&placed.try_to_vec()?);
+        Ok(())
+    }
+
     pub fn cancel_order(ctx: Context<CancelOrderCaller>, side: Side, order_id: u128) -> Result<()> {
         zeta_client::cancel_order(
             ctx.accounts.zeta_program.clone(),
@@ -117,6 +462,102 @@ pub mod zeta_cpi {
         )
     }
 
+    /// Cancels every order of a margin account flagged for force cancellation
+    /// or short of maintenance margin including orders. Anyone can call it.
+    pub fn force_cancel_orders(ctx: Context<ForceCancelOrdersCaller>) -> Result<()> {
+        {
+            let accounts = &ctx.accounts.force_cancel_orders_cpi_accounts;
+            let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(
+                &accounts.cancel_accounts.zeta_group,
+            )?;
+            let greeks = deserialize_account_info_zerocopy::<Greeks>(&accounts.greeks)?;
+            let margin_account = deserialize_account_info_zerocopy::<MarginAccount>(
+                &accounts.cancel_accounts.margin_account,
+            )?;
+            validate_force_cancellable(
+                &zeta_group,
+                &margin_account,
+                &greeks,
+                get_native_oracle_price(
+                    load_zeta_group_oracle_source(&zeta_group, &accounts.oracle)?.as_ref(),
+                )?,
+            )?;
+        }
+
+        zeta_client::force_cancel_orders(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.force_cancel_orders_cpi_accounts.clone(),
+            None,
+        )
+    }
+
+    pub fn cancel_order_by_client_order_id(
+        ctx: Context<CancelOrderCaller>,
+        client_order_id: u64,
+    ) -> Result<()> {
+        zeta_client::cancel_order_by_client_order_id(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.cancel_order_cpi_accounts.clone(),
+            None,
+            client_order_id,
+        )
+    }
+
+    /// Cancels the order `cancel_client_order_id` and places its replacement
+    /// in the same instruction, so no exposure is left between the two. Both
+    /// have to target the same market and margin account. Nothing is placed
+    /// if the order is no longer on the book.
+    pub fn cancel_replace<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, CancelReplaceCaller<'info>>,
+        cancel_client_order_id: u64,
+        price: u64,
+        size: u64,
+        side: Side,
+        order_type: OrderType,
+        client_order_id: Option<u64>,
+        tag: Option<String>,
+    ) -> Result<()> {
+        {
+            let cancel_accounts = &ctx.accounts.cancel_order_cpi_accounts.cancel_accounts;
+            let place_accounts = &ctx.accounts.place_order_cpi_accounts;
+            if cancel_accounts.market.key() != place_accounts.market_accounts.market.key()
+                || cancel_accounts.margin_account.key() != place_accounts.margin_account.key()
+            {
+                return wrap_error!(Err(error!(FuzeErrorCode::InvalidCancelReplaceAccounts)));
+            }
+        }
+
+        let (price, size) = round_order_increments(
+            &ctx.accounts.place_order_cpi_accounts.market_accounts.market,
+            price,
+            size,
+            side,
+        )?;
+        zeta_client::cancel_order_by_client_order_id(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.cancel_order_cpi_accounts.clone(),
+            None,
+            cancel_client_order_id,
+        )?;
+        place_and_record_order(
+            &ctx.accounts.place_order_cpi_accounts,
+            ctx.remaining_accounts.first(),
+            || {
+                zeta_client::place_order_v3(
+                    ctx.accounts.zeta_program.clone(),
+                    ctx.accounts.place_order_cpi_accounts.clone(),
+                    None,
+                    price,
+                    size,
+                    side,
+                    order_type,
+                    client_order_id,
+                    tag,
+                )
+            },
+        )
+    }
+
     pub fn cancel_all_market_orders(ctx: Context<CancelOrderCaller>) -> Result<()> {
         zeta_client::cancel_all_market_orders(
             ctx.accounts.zeta_program.clone(),
@@ -125,9 +566,82 @@ pub mod zeta_cpi {
         )
     }
 
-    pub fn read_program_data(ctx: Context<ReadProgramData>) -> Result<()> {
-        let zeta_group =
-            deserialize_account_info_zerocopy::<ZetaGroup>(&ctx.accounts.zeta_group).unwrap();
+    /// Liquidates `size` lots of the account's position in `market_index`.
+    /// Zeta picks the product from the `market` account, which has to match.
+    pub fn liquidate(ctx: Context<LiquidateCaller>, size: u64, market_index: u8) -> Result<()> {
+        {
+            let accounts = &ctx.accounts.liquidate_cpi_accounts;
+            let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&accounts.zeta_group)?;
+            let market_index = market_index as usize;
+            if market_index >= ACTIVE_MARKETS
+                || zeta_group.products[market_index].market != accounts.market.key()
+            {
+                return wrap_error!(Err(error!(FuzeErrorCode::InvalidMarketIndex)));
+            }
+
+            let greeks = deserialize_account_info_zerocopy::<Greeks>(&accounts.greeks)?;
+            let margin_account = deserialize_account_info_zerocopy::<MarginAccount>(
+                &accounts.liquidated_margin_account,
+            )?;
+            validate_liquidatable(
+                &zeta_group,
+                &margin_account,
+                &greeks,
+                get_native_oracle_price(
+                    load_zeta_group_oracle_source(&zeta_group, &accounts.oracle)?.as_ref(),
+                )?,
+            )?;
+        }
+
+        zeta_client::liquidate(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.liquidate_cpi_accounts.clone(),
+            None,
+            size,
+        )
+    }
+
+    /// Processes the market's event queue for the open orders accounts given
+    /// as remaining accounts.
+    pub fn crank_event_queue<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, CrankEventQueueCaller<'info>>,
+    ) -> Result<()> {
+        validate_open_orders(
+            ctx.remaining_accounts,
+            ctx.accounts.crank_event_queue_cpi_accounts.dex_program.key,
+        )?;
+        zeta_client::crank_event_queue(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.crank_event_queue_cpi_accounts.clone(),
+            None,
+            ctx.remaining_accounts,
+        )
+    }
+
+    /// Settles the DEX funds of the open orders accounts given as remaining
+    /// accounts back into the Zeta vaults.
+    pub fn settle_dex_funds<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, SettleDexFundsCaller<'info>>,
+    ) -> Result<()> {
+        validate_open_orders(
+            ctx.remaining_accounts,
+            ctx.accounts.settle_dex_funds_cpi_accounts.dex_program.key,
+        )?;
+        zeta_client::settle_dex_funds(
+            ctx.accounts.zeta_program.clone(),
+            ctx.accounts.settle_dex_funds_cpi_accounts.clone(),
+            None,
+            ctx.remaining_accounts,
+        )
+    }
+
+    /// Logs the accounts' data and the margin account state at the price
+    /// picked by `price_mode`.
+    pub fn read_program_data<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, ReadProgramData<'info>>,
+        price_mode: PriceMode,
+    ) -> Result<()> {
+        let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&ctx.accounts.zeta_group)?;
 
         // Get the data for the front expiration.
         let expiry_index = zeta_group.front_expiry_index as usize;
@@ -176,16 +690,19 @@ pub mod zeta_cpi {
             ));
         }
 
+        // The oracle account is read with the layout of its owner (Pyth or Switchboard).
+        let oracle = load_zeta_group_oracle_source(&zeta_group, &ctx.accounts.oracle)?;
+
         // This returns the oracle price as a fixed point integer with 6 decimals of precision
-        let oracle_price = get_native_oracle_price(&ctx.accounts.oracle);
-        msg!("Oracle price {:?}", oracle_price);
+        let oracle_price = get_native_oracle_price(oracle.as_ref())?;
+        msg!("Oracle price {:?}", oracle_price.get());
 
         // Get the mark price and greek data for the first product in the expiry series.
         // This happens to be the lowest strike call.
         let product_index = 0;
         let market_index = get_products_slice_market_index(expiry_index, product_index);
 
-        let greeks = deserialize_account_info_zerocopy::<Greeks>(&ctx.accounts.greeks).unwrap();
+        let greeks = deserialize_account_info_zerocopy::<Greeks>(&ctx.accounts.greeks)?;
         let market_mark_prices = greeks.get_mark_prices_slice(expiry_index)[product_index];
         let market_product_greeks = greeks.get_product_greeks_slice(expiry_index)[product_index];
 
@@ -193,14 +710,13 @@ pub mod zeta_cpi {
             "Market index = {}, Mark price = {}, Delta = {}, Vega = {:?}, IV = {:?}",
             market_index,
             market_mark_prices,
-            market_product_greeks.delta,
+            market_product_greeks.get_delta().to_decimal(),
             Decimal::from(market_product_greeks.vega),
             Decimal::from(market_product_greeks.volatility)
         ));
 
         let margin_account =
-            deserialize_account_info_zerocopy::<MarginAccount>(&ctx.accounts.margin_account)
-                .unwrap();
+            deserialize_account_info_zerocopy::<MarginAccount>(&ctx.accounts.margin_account)?;
 
         // Position details for a given market index.
         let size = margin_account.product_ledgers[market_index].position.size;
@@ -216,32 +732,104 @@ pub mod zeta_cpi {
         );
 
         let initial_margin_requirement =
-            margin_account.get_initial_margin(&greeks, &zeta_group, oracle_price);
+            margin_account.get_initial_margin(&greeks, &zeta_group, oracle_price)?;
         let maintenance_margin_requirement =
-            margin_account.get_maintenance_margin(&greeks, &zeta_group, oracle_price);
-        let total_margin_requirement = initial_margin_requirement
-            .checked_add(maintenance_margin_requirement)
-            .unwrap();
+            margin_account.get_maintenance_margin(&greeks, &zeta_group, oracle_price)?;
+        let total_margin_requirement =
+            initial_margin_requirement.checked_add(maintenance_margin_requirement)?;
 
         msg!(
             "Margin account: Initial: {}, Maintenance: {}, Total: {}",
-            initial_margin_requirement,
-            maintenance_margin_requirement,
-            total_margin_requirement
+            initial_margin_requirement.get(),
+            maintenance_margin_requirement.get(),
+            total_margin_requirement.get()
         );
 
+        let state = deserialize_account_info::<State>(&ctx.accounts.state)?;
+        let reference =
+            load_reference_source(&ctx.accounts.oracle, ctx.remaining_accounts.first())?;
         let margin_account_state = calculate_margin_account_state(
+            &state,
             &zeta_group,
             &margin_account,
             &greeks,
-            &ctx.accounts.oracle,
-        );
+            oracle.as_ref(),
+            reference.as_ref(),
+            price_mode,
+        )?;
 
         msg!("Margin account state: {:?}", margin_account_state);
 
         Ok(())
     }
 
+    /// Projects the margin account state after an order, at the price picked
+    /// by `price_mode`.
+    pub fn simulate_order<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, SimulateOrder<'info>>,
+        market_index: u8,
+        side: Side,
+        price: u64,
+        size: u64,
+        price_mode: PriceMode,
+    ) -> Result<()> {
+        let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&ctx.accounts.zeta_group)?;
+        let greeks = deserialize_account_info_zerocopy::<Greeks>(&ctx.accounts.greeks)?;
+        let margin_account =
+            deserialize_account_info_zerocopy::<MarginAccount>(&ctx.accounts.margin_account)?;
+        let state = deserialize_account_info::<State>(&ctx.accounts.state)?;
+        let oracle = load_zeta_group_oracle_source(&zeta_group, &ctx.accounts.oracle)?;
+        let reference =
+            load_reference_source(&ctx.accounts.oracle, ctx.remaining_accounts.first())?;
+
+        let margin_account_state = get_price_mode_state(
+            oracle.as_ref(),
+            reference.as_ref(),
+            price_mode,
+            |spot_price| {
+                margin_account.simulate_order(
+                    market_index.into(),
+                    side,
+                    NativePrice(price),
+                    Lots(size),
+                    &greeks,
+                    &zeta_group,
+                    spot_price,
+                    Some(state.margin_concession_percentage),
+                )
+            },
+        )?;
+        msg!("Simulated margin account state: {:?}", margin_account_state);
+
+        // Clients read the projected state from the transaction's return data.
+        set_return_data(&margin_account_state.try_to_vec()?);
+        Ok(())
+    }
+
+    /// Scans the margin accounts given as remaining accounts and writes the
+    /// keys of those whose orders can be force cancelled to the return data.
+    pub fn find_force_cancellable_accounts<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, FindForceCancellableAccounts<'info>>,
+    ) -> Result<()> {
+        let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&ctx.accounts.zeta_group)?;
+        let greeks = deserialize_account_info_zerocopy::<Greeks>(&ctx.accounts.greeks)?;
+        let spot_price = get_native_oracle_price(
+            load_zeta_group_oracle_source(&zeta_group, &ctx.accounts.oracle)?.as_ref(),
+        )?;
+
+        let force_cancellable = get_force_cancellable_accounts(
+            ctx.accounts.zeta_program.key,
+            &zeta_group,
+            &greeks,
+            spot_price,
+            ctx.remaining_accounts,
+        )?;
+        msg!("Force cancellable accounts: {}", force_cancellable.len());
+
+        set_return_data(&force_cancellable.try_to_vec()?);
+        Ok(())
+    }
+
     pub fn position_movement(
         ctx: Context<PositionMovementCaller>,
         movement_type: MovementType,
@@ -285,4 +873,74 @@ pub enum FuzeErrorCode {
     ProductDirty,
     #[msg("Invalid option kind, must be Call or Put")]
     InvalidOptionKind,
+    #[msg("Invalid market index")]
+    InvalidMarketIndex,
+    #[msg("Invalid side, must be Bid or Ask")]
+    InvalidSide,
+    #[msg("Spread account has unbounded risk")]
+    SpreadAccountUndefinedRisk,
+    #[msg("Math operation overflowed")]
+    MathOverflow,
+    #[msg("Math operation underflowed")]
+    MathUnderflow,
+    #[msg("Math operation divided by zero")]
+    DivisionByZero,
+    #[msg("Margin account is not a market maker account")]
+    AccountNotMarketMaker,
+    #[msg("Margin concession percentage must be between 1 and 100")]
+    InvalidMarginConcessionPercentage,
+    #[msg("Trade ledger does not belong to the margin account")]
+    InvalidTradeLedger,
+    #[msg("Price must be greater than zero")]
+    InvalidPrice,
+    #[msg("Margin account is above maintenance margin")]
+    AccountNotLiquidatable,
+    #[msg("Invalid oracle account")]
+    InvalidOracleAccount,
+    #[msg("Oracle price is not trading")]
+    OraclePriceNotTrading,
+    #[msg("Oracle price is stale")]
+    StaleOraclePrice,
+    #[msg("Oracle confidence interval is too wide")]
+    OracleConfidenceTooWide,
+    #[msg("Oracle price must be positive")]
+    InvalidOraclePrice,
+    #[msg("Oracle account owner is not a supported oracle program")]
+    UnsupportedOracle,
+    #[msg("Oracle does not publish a reference price")]
+    ReferencePriceUnavailable,
+    #[msg("Oracle EMA window must be greater than zero")]
+    InvalidOracleEmaWindow,
+    #[msg("Cancel and place accounts must share the market and margin account")]
+    InvalidCancelReplaceAccounts,
+    #[msg("Expected writable DEX open orders accounts as remaining accounts")]
+    InvalidOpenOrdersAccounts,
+    #[msg("Account still has positions, orders or balance")]
+    AccountNotEmpty,
+    #[msg("Account has active orders")]
+    AccountHasActiveOrders,
+    #[msg("Account is neither flagged nor below maintenance margin including orders")]
+    AccountNotForceCancellable,
+    #[msg("Order batch is empty")]
+    EmptyOrderBatch,
+    #[msg("Order batch does not fit the account's initial margin")]
+    InsufficientMarginForOrders,
+    #[msg("Expected a group of market accounts for every market in the batch")]
+    InvalidPlaceOrdersAccounts,
+    #[msg("Time in force offset must be greater than zero")]
+    InvalidTifOffset,
+    #[msg("Time in force ends after the expiry of the series")]
+    TifExceedsExpiry,
+    #[msg("Market account is not an initialized DEX market")]
+    InvalidDexMarket,
+    #[msg("Price must be a positive multiple of the market tick size")]
+    InvalidTickSize,
+    #[msg("Size must be a positive multiple of the market lot size")]
+    InvalidLotSize,
+    #[msg("Oracle EMA does not track the oracle")]
+    InvalidOracleEma,
+    #[msg("Market has no margin or premium cost per lot to size an order against")]
+    UnpricedMarket,
+    #[msg("Time to expiry must be greater than zero")]
+    InvalidTimeToExpiry,
 }
@@ -0,0 +1,469 @@
+use crate::pyth_client::{Price, PriceStatus, PriceType};
+use crate::pyth_price_update::{PriceUpdateV2, VerificationLevel};
+use crate::switchboard_client::AggregatorAccountData;
+use crate::*;
+use std::cell::RefMut;
+use std::convert::{TryFrom, TryInto};
+
+/// Validation applied to oracle reads.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct OracleConfig {
+    // Max slots between the aggregate price and the current slot.
+    pub max_staleness_slots: u64,
+    // Max confidence interval relative to price, NATIVE_PRECISION_DENOMINATOR = 100%.
+    pub max_confidence_percentage: u64,
+}
+
+impl Default for OracleConfig {
+    fn default() -> Self {
+        OracleConfig {
+            max_staleness_slots: DEFAULT_ORACLE_MAX_STALENESS_SLOTS,
+            max_confidence_percentage: DEFAULT_ORACLE_MAX_CONFIDENCE_PERCENTAGE,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: u64,      // Aggregate price at the requested precision.
+    pub confidence: u64, // Aggregate confidence interval at the same precision.
+    pub valid_slot: u64, // Slot the aggregate price is valid for.
+}
+
+/// Price margin calculations are run at.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceMode {
+    // Aggregate price.
+    Spot,
+    // Time weighted reference price, see `OracleSource::get_reference_price`.
+    Twap,
+    // Whichever of the two gives the worse margin account state.
+    Conservative,
+}
+
+/// A price feed backing the spot price of margin calculations.
+///
+/// Implemented for every supported oracle layout, `load_oracle_source`
+/// picks one by account owner. Tests can implement it for a fake oracle.
+pub trait OracleSource {
+    /// Validates the feed as read at `current_slot` and returns its price
+    /// with `precision` decimals.
+    fn get_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice>;
+
+    /// Same as `get_price` for the feed's time weighted price (Pyth TWAP or
+    /// EMA), which a single print can't move. Not every feed publishes one.
+    fn get_reference_price(
+        &self,
+        _config: &OracleConfig,
+        _current_slot: u64,
+        _precision: u32,
+    ) -> Result<OraclePrice> {
+        wrap_error!(Err(error!(FuzeErrorCode::ReferencePriceUnavailable)))
+    }
+}
+
+/// Oracle programs whose accounts can back a price.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OracleProgram {
+    Pyth,
+    PythReceiver,
+    SwitchboardV2,
+}
+
+impl OracleProgram {
+    pub fn from_owner(owner: &Pubkey) -> Result<OracleProgram> {
+        if *owner == address::pyth_oracle::ID {
+            Ok(OracleProgram::Pyth)
+        } else if *owner == address::pyth_receiver::ID {
+            Ok(OracleProgram::PythReceiver)
+        } else if *owner == address::switchboard_v2::ID {
+            Ok(OracleProgram::SwitchboardV2)
+        } else {
+            wrap_error!(Err(error!(FuzeErrorCode::UnsupportedOracle)))
+        }
+    }
+}
+
+/// Rescales `value * 10^expo` to `precision` decimals.
+pub fn rescale(value: u128, expo: i32, precision: u32) -> Result<u64> {
+    let shift = (precision as i64)
+        .checked_add(expo.into())
+        .ok_or_else(math_error!(MathOverflow))?;
+    let factor = 10u128
+        .checked_pow(
+            shift
+                .unsigned_abs()
+                .try_into()
+                .map_err(|_| error!(FuzeErrorCode::MathOverflow))?,
+        )
+        .ok_or_else(math_error!(MathOverflow))?;
+
+    if shift >= 0 {
+        value.checked_mul(factor)
+    } else {
+        value.checked_div(factor)
+    }
+    .ok_or_else(math_error!(MathOverflow))?
+    .try_into()
+    .map_err(|_| error!(FuzeErrorCode::MathOverflow))
+}
+
+/// Checks shared by every source, on a price rescaled to the requested
+/// precision: staleness, a non zero price and the confidence interval.
+pub fn validate_oracle_price(
+    oracle_price: OraclePrice,
+    config: &OracleConfig,
+    current_slot: u64,
+) -> Result<OraclePrice> {
+    if current_slot.saturating_sub(oracle_price.valid_slot) > config.max_staleness_slots {
+        return wrap_error!(Err(error!(FuzeErrorCode::StaleOraclePrice)));
+    }
+    // A price that rounds to zero at this precision is as unusable as a negative one.
+    if oracle_price.price == 0 {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidOraclePrice)));
+    }
+
+    let max_confidence = (oracle_price.price as u128)
+        .checked_mul(config.max_confidence_percentage as u128)
+        .ok_or_else(math_error!(MathOverflow))?
+        .checked_div(NATIVE_PRECISION_DENOMINATOR)
+        .ok_or_else(math_error!(DivisionByZero))?;
+    if oracle_price.confidence as u128 > max_confidence {
+        return wrap_error!(Err(error!(FuzeErrorCode::OracleConfidenceTooWide)));
+    }
+    Ok(oracle_price)
+}
+
+impl Price {
+    /// Checks the account header and that the aggregate price is trading.
+    fn validate_trading(&self) -> Result<()> {
+        if self.magic != PYTH_MAGIC
+            || self.ver != PYTH_VERSION
+            || self.atype != PYTH_ACCOUNT_TYPE_PRICE
+            || self.ptype != PriceType::Price as u32
+        {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidOracleAccount)));
+        }
+        if self.agg.status != PriceStatus::Trading as u32 {
+            return wrap_error!(Err(error!(FuzeErrorCode::OraclePriceNotTrading)));
+        }
+        Ok(())
+    }
+}
+
+/// Legacy Pyth price account, see `pyth_client`.
+impl OracleSource for Price {
+    fn get_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        self.validate_trading()?;
+        let agg = self.agg;
+        if agg.price <= 0 {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidOraclePrice)));
+        }
+
+        validate_oracle_price(
+            OraclePrice {
+                price: rescale(agg.price as u128, self.expo, precision)?,
+                confidence: rescale(agg.conf as u128, self.expo, precision)?,
+                valid_slot: self.valid_slot,
+            },
+            config,
+            current_slot,
+        )
+    }
+
+    /// The account has no TWAP confidence, the aggregate one is used.
+    fn get_reference_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        self.validate_trading()?;
+        if self.twap <= 0 {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidOraclePrice)));
+        }
+
+        validate_oracle_price(
+            OraclePrice {
+                price: rescale(self.twap as u128, self.expo, precision)?,
+                confidence: rescale(self.agg.conf as u128, self.expo, precision)?,
+                valid_slot: self.valid_slot,
+            },
+            config,
+            current_slot,
+        )
+    }
+}
+
+/// Pyth receiver price update. Only fully verified updates are accepted,
+/// staleness is measured from the slot the update was posted at.
+impl OracleSource for PriceUpdateV2 {
+    fn get_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        let message = self.price_message;
+        self.get_message_price(message.price, message.conf, config, current_slot, precision)
+    }
+
+    fn get_reference_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        let message = self.price_message;
+        self.get_message_price(
+            message.ema_price,
+            message.ema_conf,
+            config,
+            current_slot,
+            precision,
+        )
+    }
+}
+
+impl PriceUpdateV2 {
+    fn get_message_price(
+        &self,
+        price: i64,
+        conf: u64,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        if self.verification_level != VerificationLevel::Full {
+            return wrap_error!(Err(error!(FuzeErrorCode::OraclePriceNotTrading)));
+        }
+        if price <= 0 {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidOraclePrice)));
+        }
+
+        let exponent = self.price_message.exponent;
+        validate_oracle_price(
+            OraclePrice {
+                price: rescale(price as u128, exponent, precision)?,
+                confidence: rescale(conf as u128, exponent, precision)?,
+                valid_slot: self.posted_slot,
+            },
+            config,
+            current_slot,
+        )
+    }
+}
+
+/// Switchboard V2 aggregator. The result of the latest confirmed round is
+/// the price and its standard deviation the confidence interval.
+impl OracleSource for AggregatorAccountData {
+    fn get_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        let round = self.latest_confirmed_round;
+        if round.num_success < self.min_oracle_results {
+            return wrap_error!(Err(error!(FuzeErrorCode::OraclePriceNotTrading)));
+        }
+
+        let result = round.result;
+        let std_deviation = round.std_deviation;
+        if result.mantissa <= 0 || std_deviation.mantissa < 0 {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidOraclePrice)));
+        }
+        let result_expo = -i32::try_from(result.scale)
+            .map_err(|_| error!(FuzeErrorCode::InvalidOracleAccount))?;
+        let std_deviation_expo = -i32::try_from(std_deviation.scale)
+            .map_err(|_| error!(FuzeErrorCode::InvalidOracleAccount))?;
+
+        validate_oracle_price(
+            OraclePrice {
+                price: rescale(result.mantissa as u128, result_expo, precision)?,
+                confidence: rescale(
+                    std_deviation.mantissa as u128,
+                    std_deviation_expo,
+                    precision,
+                )?,
+                valid_slot: round.round_open_slot,
+            },
+            config,
+            current_slot,
+        )
+    }
+}
+
+/// Account data borrowed in place, Pyth price accounts are too large to copy.
+impl<'a, T: OracleSource> OracleSource for RefMut<'a, T> {
+    fn get_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        (**self).get_price(config, current_slot, precision)
+    }
+
+    fn get_reference_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        (**self).get_reference_price(config, current_slot, precision)
+    }
+}
+
+/// Loads `oracle` with the layout of the program owning it.
+pub fn load_oracle_source<'a>(oracle: &'a AccountInfo) -> Result<Box<dyn OracleSource + 'a>> {
+    Ok(match OracleProgram::from_owner(oracle.owner)? {
+        OracleProgram::Pyth => Box::new(Price::load(oracle)?),
+        OracleProgram::PythReceiver => Box::new(PriceUpdateV2::load(oracle)?),
+        OracleProgram::SwitchboardV2 => Box::new(AggregatorAccountData::load(oracle)?),
+    })
+}
+
+/// Loads `oracle` after checking it is the oracle of `zeta_group`. The layout
+/// is picked by owner alone, so any price account of a supported oracle
+/// program would otherwise be read as the group's spot price.
+pub fn load_zeta_group_oracle_source<'a>(
+    zeta_group: &ZetaGroup,
+    oracle: &'a AccountInfo,
+) -> Result<Box<dyn OracleSource + 'a>> {
+    if oracle.key() != zeta_group.oracle {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidOracleAccount)));
+    }
+    load_oracle_source(oracle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    fn trading_price(price: i64, conf: u64, expo: i32) -> Price {
+        let mut account = Price::default();
+        account.magic = PYTH_MAGIC;
+        account.ver = PYTH_VERSION;
+        account.atype = PYTH_ACCOUNT_TYPE_PRICE;
+        account.ptype = PriceType::Price as u32;
+        account.expo = expo;
+        account.valid_slot = 100;
+        account.agg.price = price;
+        account.agg.conf = conf;
+        account.agg.status = PriceStatus::Trading as u32;
+        account
+    }
+
+    #[test]
+    fn test_rescale() {
+        assert_eq!(rescale(2_512_345_678, -8, 6).unwrap(), 25_123_456);
+        assert_eq!(rescale(25, 0, 6).unwrap(), 25_000_000);
+        assert_eq!(rescale(25, 2, 0).unwrap(), 2_500);
+        assert_eq!(rescale(25_123_456, -6, 6).unwrap(), 25_123_456);
+        assert!(rescale(1, 40, 0).is_err());
+        assert!(rescale(u64::MAX as u128 + 1, -6, 6).is_err());
+    }
+
+    #[test]
+    fn test_validate_oracle_price() {
+        let config = OracleConfig::default();
+        let oracle_price = OraclePrice {
+            price: 100_000_000,
+            confidence: 2_000_000,
+            valid_slot: 100,
+        };
+        assert_eq!(
+            validate_oracle_price(oracle_price, &config, 125).unwrap(),
+            oracle_price
+        );
+        assert!(validate_oracle_price(oracle_price, &config, 126).is_err());
+        assert!(validate_oracle_price(
+            OraclePrice {
+                confidence: 2_000_001,
+                ..oracle_price
+            },
+            &config,
+            100
+        )
+        .is_err());
+        assert!(validate_oracle_price(
+            OraclePrice {
+                price: 0,
+                ..oracle_price
+            },
+            &config,
+            100
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_pyth_price() {
+        let config = OracleConfig::default();
+        let account = trading_price(2_512_345_678, 1_000_000, -8);
+        assert_eq!(
+            account.get_price(&config, 100, PLATFORM_PRECISION).unwrap(),
+            OraclePrice {
+                price: 25_123_456,
+                confidence: 10_000,
+                valid_slot: 100,
+            }
+        );
+
+        let mut halted = account;
+        halted.agg.status = PriceStatus::Halted as u32;
+        assert!(halted.get_price(&config, 100, PLATFORM_PRECISION).is_err());
+
+        let mut unknown_status = account;
+        unknown_status.agg.status = 7;
+        assert!(unknown_status
+            .get_price(&config, 100, PLATFORM_PRECISION)
+            .is_err());
+
+        let negative = trading_price(-1, 0, -8);
+        assert!(negative
+            .get_price(&config, 100, PLATFORM_PRECISION)
+            .is_err());
+    }
+
+    #[test]
+    fn test_load_zeta_group_oracle_source() {
+        let oracle_key = Pubkey::new_unique();
+        let mut zeta_group = ZetaGroup::zeroed();
+        zeta_group.oracle = oracle_key;
+
+        let mut lamports = 0;
+        let mut data = bytemuck::bytes_of(&trading_price(2_512_345_678, 1_000_000, -8)).to_vec();
+        let oracle = AccountInfo::new(
+            &oracle_key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &address::pyth_oracle::ID,
+            false,
+            0,
+        );
+        let price = load_zeta_group_oracle_source(&zeta_group, &oracle)
+            .unwrap()
+            .get_price(&OracleConfig::default(), 100, PLATFORM_PRECISION)
+            .unwrap()
+            .price;
+        assert_eq!(price, 25_123_456);
+
+        zeta_group.oracle = Pubkey::new_unique();
+        assert!(load_zeta_group_oracle_source(&zeta_group, &oracle).is_err());
+    }
+}
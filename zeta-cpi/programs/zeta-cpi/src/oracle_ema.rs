@@ -0,0 +1,171 @@
+use crate::*;
+use std::convert::{TryFrom, TryInto};
+
+/// Exponential moving average of an oracle's price, kept by zeta-cpi for
+/// oracles without a usable TWAP. PDA of
+/// `[ORACLE_EMA_SEED, oracle, window_seconds]`, so every window has its own
+/// average, updated by the `update_oracle_ema` crank.
+#[account(zero_copy)]
+#[repr(packed)]
+pub struct OracleEma {
+    pub nonce: u8,             // 1
+    pub oracle: Pubkey,        // 32
+    pub window_seconds: u64,   // 8
+    pub ema_price: u64,        // 8 (6.dp)
+    pub last_update_slot: u64, // 8
+    pub last_update_ts: i64,   // 8
+} // 65
+
+impl OracleEma {
+    /// Moves the average towards `spot_price` by the share of the window
+    /// elapsed since the last update. The first update seeds it.
+    pub fn update(&mut self, spot_price: u64, slot: u64, timestamp: i64) -> Result<()> {
+        let window_seconds = self.window_seconds;
+        if window_seconds == 0 {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidOracleEmaWindow)));
+        }
+
+        if self.ema_price == 0 {
+            self.ema_price = spot_price;
+        } else {
+            let elapsed = u64::try_from(timestamp.saturating_sub(self.last_update_ts)).unwrap_or(0);
+            let weight = elapsed.min(window_seconds);
+            let ema_price = self.ema_price as i128;
+            self.ema_price = (spot_price as i128)
+                .checked_sub(ema_price)
+                .ok_or_else(math_error!(MathUnderflow))?
+                .checked_mul(weight as i128)
+                .ok_or_else(math_error!(MathOverflow))?
+                .checked_div(window_seconds as i128)
+                .ok_or_else(math_error!(DivisionByZero))?
+                .checked_add(ema_price)
+                .ok_or_else(math_error!(MathOverflow))?
+                .try_into()
+                .map_err(|_| error!(FuzeErrorCode::MathOverflow))?;
+        }
+
+        self.last_update_slot = slot;
+        self.last_update_ts = timestamp;
+        Ok(())
+    }
+}
+
+/// The average is both the price and the reference price. Staleness is
+/// measured from the last crank and allowed up to
+/// `ORACLE_EMA_MAX_STALENESS_SLOTS` even under a stricter `config`, which is
+/// meant for spot prints.
+impl OracleSource for OracleEma {
+    fn get_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        validate_oracle_price(
+            OraclePrice {
+                price: rescale(
+                    self.ema_price as u128,
+                    -(PLATFORM_PRECISION as i32),
+                    precision,
+                )?,
+                confidence: 0,
+                valid_slot: self.last_update_slot,
+            },
+            &OracleConfig {
+                max_staleness_slots: config
+                    .max_staleness_slots
+                    .max(ORACLE_EMA_MAX_STALENESS_SLOTS),
+                ..*config
+            },
+            current_slot,
+        )
+    }
+
+    fn get_reference_price(
+        &self,
+        config: &OracleConfig,
+        current_slot: u64,
+        precision: u32,
+    ) -> Result<OraclePrice> {
+        self.get_price(config, current_slot, precision)
+    }
+}
+
+/// Loads the reference price source of a margin calculation on `oracle`:
+/// `oracle_ema` when given, which has to be an `OracleEma` tracking `oracle`,
+/// or else `oracle` itself for its own TWAP / EMA.
+pub fn load_reference_source<'a>(
+    oracle: &'a AccountInfo,
+    oracle_ema: Option<&AccountInfo>,
+) -> Result<Box<dyn OracleSource + 'a>> {
+    let oracle_ema = match oracle_ema {
+        Some(oracle_ema) => *AccountLoader::<OracleEma>::try_from(oracle_ema)?.load()?,
+        None => return load_oracle_source(oracle),
+    };
+    if oracle_ema.oracle != oracle.key() {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidOracleEma)));
+    }
+    Ok(Box::new(oracle_ema))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    fn oracle_ema(window_seconds: u64) -> OracleEma {
+        let mut oracle_ema = OracleEma::zeroed();
+        oracle_ema.window_seconds = window_seconds;
+        oracle_ema
+    }
+
+    #[test]
+    fn test_update() {
+        let mut ema = oracle_ema(100);
+        ema.update(50_000_000, 10, 1_000).unwrap();
+        assert_eq!({ ema.ema_price }, 50_000_000);
+
+        // A quarter of the window moves the average a quarter of the way.
+        ema.update(60_000_000, 20, 1_025).unwrap();
+        assert_eq!({ ema.ema_price }, 52_500_000);
+        assert_eq!({ ema.last_update_slot }, 20);
+        assert_eq!({ ema.last_update_ts }, 1_025);
+
+        // Past a full window the average is reset to spot.
+        ema.update(40_000_000, 30, 2_000).unwrap();
+        assert_eq!({ ema.ema_price }, 40_000_000);
+
+        // Updates in the same second or from an earlier clock don't move it.
+        ema.update(80_000_000, 31, 1_990).unwrap();
+        assert_eq!({ ema.ema_price }, 40_000_000);
+
+        assert!(oracle_ema(0).update(50_000_000, 10, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_get_price() {
+        let mut ema = oracle_ema(3_600);
+        ema.update(50_000_000, 100, 1_000).unwrap();
+
+        let config = OracleConfig::default();
+        let price = ema
+            .get_price(
+                &config,
+                100 + ORACLE_EMA_MAX_STALENESS_SLOTS,
+                PLATFORM_PRECISION,
+            )
+            .unwrap();
+        assert_eq!(price.price, 50_000_000);
+        assert_eq!(
+            ema.get_reference_price(&config, 100, 2).unwrap().price,
+            5_000
+        );
+        assert!(ema
+            .get_price(
+                &config,
+                101 + ORACLE_EMA_MAX_STALENESS_SLOTS,
+                PLATFORM_PRECISION
+            )
+            .is_err());
+    }
+}
@@ -1,7 +1,7 @@
 // Taken from https://github.com/project-serum/anchor/tree/master/examples/pyth
 use crate::*;
 use anchor_lang::prelude::AccountInfo;
-use bytemuck::{cast_slice_mut, from_bytes_mut, try_cast_slice_mut, Pod, Zeroable};
+use bytemuck::{cast_slice_mut, from_bytes_mut, Pod, Zeroable};
 use std::cell::RefMut;
 
 #[derive(Default, Copy, Clone)]
@@ -19,31 +19,19 @@ pub enum PriceStatus {
     Auction,
 }
 
-impl Default for PriceStatus {
-    fn default() -> Self {
-        PriceStatus::Trading
-    }
-}
-
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub enum CorpAction {
     NoCorpAct,
 }
 
-impl Default for CorpAction {
-    fn default() -> Self {
-        CorpAction::NoCorpAct
-    }
-}
-
 #[derive(Default, Copy, Clone)]
 #[repr(C)]
 pub struct PriceInfo {
     pub price: i64,
     pub conf: u64,
-    pub status: PriceStatus,
-    pub corp_act: CorpAction,
+    pub status: u32,   // PriceStatus.
+    pub corp_act: u32, // CorpAction.
     pub pub_slot: u64,
 }
 #[derive(Default, Copy, Clone)]
@@ -63,22 +51,18 @@ pub enum PriceType {
     Volatility,
 }
 
-impl Default for PriceType {
-    fn default() -> Self {
-        PriceType::Price
-    }
-}
-
+// Enum fields are mirrored as their u32 values, an account holding any
+// other value would otherwise be undefined behaviour to read.
 #[derive(Default, Copy, Clone)]
 #[repr(C)]
 pub struct Price {
-    pub magic: u32,       // Pyth magic number.
-    pub ver: u32,         // Program version.
-    pub atype: u32,       // Account type.
-    pub size: u32,        // Price account size.
-    pub ptype: PriceType, // Price or calculation type.
-    pub expo: i32,        // Price exponent.
-    pub num: u32,         // Number of component prices.
+    pub magic: u32, // Pyth magic number.
+    pub ver: u32,   // Program version.
+    pub atype: u32, // Account type.
+    pub size: u32,  // Price account size.
+    pub ptype: u32, // PriceType, price or calculation type.
+    pub expo: i32,  // Price exponent.
+    pub num: u32,   // Number of component prices.
     pub unused: u32,
     pub curr_slot: u64,        // Currently accumulating price slot.
     pub valid_slot: u64,       // Valid slot-time of agg. price.
@@ -103,10 +87,13 @@ impl Price {
         let account_data: RefMut<'a, [u8]>;
         let state: RefMut<'a, Self>;
 
-        account_data = RefMut::map(price_feed.try_borrow_mut_data().unwrap(), |data| *data);
+        account_data = RefMut::map(price_feed.try_borrow_mut_data()?, |data| *data);
+        if account_data.len() != std::mem::size_of::<Price>() {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidOracleAccount)));
+        }
 
         state = RefMut::map(account_data, |data| {
-            from_bytes_mut(cast_slice_mut::<u8, u8>(try_cast_slice_mut(data).unwrap()))
+            from_bytes_mut(cast_slice_mut::<u8, u8>(data))
         });
         Ok(state)
     }
@@ -0,0 +1,43 @@
+// Layout of the price update accounts posted by the Pyth receiver program.
+// See https://github.com/pyth-network/pyth-crosschain/tree/main/target_chains/solana
+use crate::*;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VerificationLevel {
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Debug)]
+pub struct PriceFeedMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,      // Unix timestamp of the price.
+    pub prev_publish_time: i64, // Unix timestamp of the previous price.
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Debug)]
+pub struct PriceUpdateV2 {
+    pub write_authority: Pubkey,
+    pub verification_level: VerificationLevel,
+    pub price_message: PriceFeedMessage,
+    pub posted_slot: u64, // Slot the update was posted at.
+}
+
+impl PriceUpdateV2 {
+    pub fn load(price_update: &AccountInfo) -> Result<PriceUpdateV2> {
+        let data = price_update.try_borrow_data()?;
+        if data.len() < DISCRIMINATOR_SIZE
+            || data[..DISCRIMINATOR_SIZE] != PYTH_PRICE_UPDATE_DISCRIMINATOR
+        {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidOracleAccount)));
+        }
+
+        PriceUpdateV2::deserialize(&mut &data[DISCRIMINATOR_SIZE..])
+            .map_err(|_| error!(FuzeErrorCode::InvalidOracleAccount))
+    }
+}
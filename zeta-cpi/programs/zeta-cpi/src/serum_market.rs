@@ -0,0 +1,245 @@
+// Leading fields of the Serum DEX market account, up to its lot sizes.
+// Trailing fields are not mirrored.
+// See https://github.com/project-serum/serum-dex/blob/master/dex/src/state.rs
+use crate::*;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct MarketStateHeader {
+    pub account_flags: u64,
+    pub own_address: [u64; 4],
+    pub vault_signer_nonce: u64,
+    pub coin_mint: [u64; 4],
+    pub pc_mint: [u64; 4],
+    pub coin_vault: [u64; 4],
+    pub coin_deposits_total: u64,
+    pub coin_fees_accrued: u64,
+    pub pc_vault: [u64; 4],
+    pub pc_deposits_total: u64,
+    pub pc_fees_accrued: u64,
+    pub pc_dust_threshold: u64,
+    pub req_q: [u64; 4],
+    pub event_q: [u64; 4],
+    pub bids: [u64; 4],
+    pub asks: [u64; 4],
+    pub coin_lot_size: u64, // Native base units per coin lot.
+    pub pc_lot_size: u64,   // Native quote units per price lot.
+}
+
+impl MarketStateHeader {
+    pub fn load(market: &AccountInfo) -> Result<MarketStateHeader> {
+        MarketStateHeader::from_account_data(&market.try_borrow_data()?)
+    }
+
+    /// Same as `load` for raw account data, e.g. fetched by a client.
+    pub fn from_account_data(data: &[u8]) -> Result<MarketStateHeader> {
+        let start = SERUM_ACCOUNT_HEAD_PADDING.len();
+        let end = start + std::mem::size_of::<MarketStateHeader>();
+        if data.len() < end || data[..start] != SERUM_ACCOUNT_HEAD_PADDING[..] {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidDexMarket)));
+        }
+
+        let header = try_from_bytes::<MarketStateHeader>(&data[start..end])
+            .map(|header| *header)
+            .map_err(|_| error!(FuzeErrorCode::InvalidDexMarket))?;
+        let flags = header.account_flags;
+        if flags & SERUM_MARKET_ACCOUNT_FLAGS != SERUM_MARKET_ACCOUNT_FLAGS {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidDexMarket)));
+        }
+        Ok(header)
+    }
+}
+
+/// Price and size increments of a market in Zeta units. Zeta prices are per
+/// contract (`10^POSITION_PRECISION` base units), so a price lot of
+/// `pc_lot_size` per `coin_lot_size` is a tick of
+/// `pc_lot_size * 10^POSITION_PRECISION / coin_lot_size`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MarketIncrements {
+    pub tick_size: NativePrice,
+    pub lot_size: Lots,
+}
+
+impl MarketIncrements {
+    pub fn load(market: &AccountInfo) -> Result<MarketIncrements> {
+        MarketIncrements::from_header(&MarketStateHeader::load(market)?)
+    }
+
+    /// Markets whose tick isn't a whole native unit are refused.
+    pub fn from_header(header: &MarketStateHeader) -> Result<MarketIncrements> {
+        let coin_lot_size = header.coin_lot_size;
+        let pc_lot_size = header.pc_lot_size;
+        if coin_lot_size == 0 || pc_lot_size == 0 {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidDexMarket)));
+        }
+
+        let price_lot_size = pc_lot_size
+            .checked_mul(10u64.pow(POSITION_PRECISION))
+            .ok_or_else(math_error!(MathOverflow))?;
+        if price_lot_size % coin_lot_size != 0 {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidDexMarket)));
+        }
+
+        Ok(MarketIncrements {
+            tick_size: NativePrice(price_lot_size / coin_lot_size),
+            lot_size: Lots(coin_lot_size),
+        })
+    }
+
+    /// Rounds `price` onto a tick in the maker's favour, down for bids and up
+    /// for asks.
+    pub fn round_price(&self, price: NativePrice, side: Side) -> Result<NativePrice> {
+        let tick_size = self.tick_size.get();
+        let ticks = match side {
+            Side::Bid => price.get() / tick_size,
+            Side::Ask => {
+                price
+                    .get()
+                    .checked_add(tick_size - 1)
+                    .ok_or_else(math_error!(MathOverflow))?
+                    / tick_size
+            }
+            Side::Uninitialized => return wrap_error!(Err(error!(FuzeErrorCode::InvalidSide))),
+        };
+        ticks
+            .checked_mul(tick_size)
+            .map(NativePrice)
+            .ok_or_else(math_error!(MathOverflow))
+    }
+
+    /// Rounds `size` down to whole lots.
+    pub fn round_size(&self, size: Lots) -> Lots {
+        Lots(size.get() / self.lot_size.get() * self.lot_size.get())
+    }
+
+    pub fn validate_price(&self, price: NativePrice) -> Result<()> {
+        if price == NativePrice::ZERO || price.get() % self.tick_size.get() != 0 {
+            msg!(
+                "Price {} with tick size {}",
+                price.get(),
+                self.tick_size.get()
+            );
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidTickSize)));
+        }
+        Ok(())
+    }
+
+    pub fn validate_size(&self, size: Lots) -> Result<()> {
+        if size == Lots::ZERO || size.get() % self.lot_size.get() != 0 {
+            msg!("Size {} with lot size {}", size.get(), self.lot_size.get());
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidLotSize)));
+        }
+        Ok(())
+    }
+
+    /// Rounds an order onto whole ticks and lots, erroring if either rounds
+    /// to zero.
+    pub fn round_order(
+        &self,
+        price: NativePrice,
+        size: Lots,
+        side: Side,
+    ) -> Result<(NativePrice, Lots)> {
+        let price = self.round_price(price, side)?;
+        let size = self.round_size(size);
+        self.validate_price(price)?;
+        self.validate_size(size)?;
+        Ok((price, size))
+    }
+}
+
+/// Rounds `price` and `size` onto the ticks and lots of `market`, see
+/// `MarketIncrements::round_order`. Applied before an order CPI, which would
+/// otherwise fail inside the DEX.
+pub fn round_order_increments(
+    market: &AccountInfo,
+    price: u64,
+    size: u64,
+    side: Side,
+) -> Result<(u64, u64)> {
+    let (price, size) =
+        MarketIncrements::load(market)?.round_order(NativePrice(price), Lots(size), side)?;
+    Ok((price.get(), size.get()))
+}
+
+#[cfg(target_endian = "little")]
+unsafe impl Zeroable for MarketStateHeader {}
+
+#[cfg(target_endian = "little")]
+unsafe impl Pod for MarketStateHeader {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(coin_lot_size: u64, pc_lot_size: u64) -> MarketStateHeader {
+        MarketStateHeader {
+            coin_lot_size,
+            pc_lot_size,
+            ..MarketStateHeader::zeroed()
+        }
+    }
+
+    // A tick of 0.01 and a lot of 0.01 contracts.
+    fn increments() -> MarketIncrements {
+        MarketIncrements {
+            tick_size: NativePrice(10_000),
+            lot_size: Lots(10),
+        }
+    }
+
+    #[test]
+    fn test_from_header() {
+        // 100 native quote units per price lot of 10 base units.
+        assert_eq!(
+            MarketIncrements::from_header(&header(10, 100)).unwrap(),
+            increments()
+        );
+        assert_eq!(
+            MarketIncrements::from_header(&header(1, 1)).unwrap(),
+            MarketIncrements {
+                tick_size: NativePrice(1_000),
+                lot_size: Lots(1),
+            }
+        );
+        // A tick of a third of a native unit.
+        assert!(MarketIncrements::from_header(&header(3_000, 1)).is_err());
+        assert!(MarketIncrements::from_header(&header(0, 100)).is_err());
+        assert!(MarketIncrements::from_header(&header(10, 0)).is_err());
+    }
+
+    #[test]
+    fn test_round_price() {
+        let increments = increments();
+        let round = |price, side| increments.round_price(NativePrice(price), side);
+
+        assert_eq!(round(1_234_567, Side::Bid).unwrap(), NativePrice(1_230_000));
+        assert_eq!(round(1_234_567, Side::Ask).unwrap(), NativePrice(1_240_000));
+        assert_eq!(round(1_230_000, Side::Bid).unwrap(), NativePrice(1_230_000));
+        assert_eq!(round(1_230_000, Side::Ask).unwrap(), NativePrice(1_230_000));
+        assert_eq!(round(9_999, Side::Bid).unwrap(), NativePrice::ZERO);
+        assert!(round(u64::MAX, Side::Ask).is_err());
+        assert!(round(1_234_567, Side::Uninitialized).is_err());
+    }
+
+    #[test]
+    fn test_round_order() {
+        let increments = increments();
+        assert_eq!(increments.round_size(Lots(1_234)), Lots(1_230));
+        assert_eq!(
+            increments
+                .round_order(NativePrice(1_234_567), Lots(1_234), Side::Ask)
+                .unwrap(),
+            (NativePrice(1_240_000), Lots(1_230))
+        );
+
+        // Orders rounding to nothing are refused.
+        assert!(increments
+            .round_order(NativePrice(9_999), Lots(1_234), Side::Bid)
+            .is_err());
+        assert!(increments
+            .round_order(NativePrice(1_234_567), Lots(9), Side::Bid)
+            .is_err());
+    }
+}
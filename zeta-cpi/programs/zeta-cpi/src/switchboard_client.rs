@@ -0,0 +1,79 @@
+// Leading fields of the Switchboard V2 aggregator account, up to the result
+// of its latest confirmed round. Trailing fields are not mirrored.
+// See https://github.com/switchboard-xyz/sbv2-solana/tree/main/rust/switchboard-v2
+use crate::*;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+
+// Fixed point number, mantissa * 10^-scale.
+#[derive(Default, Copy, Clone)]
+#[repr(C, packed)]
+pub struct SwitchboardDecimal {
+    pub mantissa: i128,
+    pub scale: u32,
+}
+
+#[derive(Default, Copy, Clone)]
+#[repr(C, packed)]
+pub struct AggregatorRound {
+    pub num_success: u32,                  // Oracle responses accepted in the round.
+    pub num_error: u32,                    // Oracle responses rejected in the round.
+    pub is_closed: u8,                     // Bool.
+    pub round_open_slot: u64,              // Slot the round was opened at.
+    pub round_open_timestamp: i64,         // Unix timestamp the round was opened at.
+    pub result: SwitchboardDecimal,        // Median of the accepted responses.
+    pub std_deviation: SwitchboardDecimal, // Standard deviation of the accepted responses.
+}
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+pub struct AggregatorAccountData {
+    pub name: [u8; 32],
+    pub metadata: [u8; 128],
+    pub reserved1: [u8; 32],
+    pub queue_pubkey: [u8; 32],
+    pub oracle_request_batch_size: u32,
+    pub min_oracle_results: u32, // Responses required to confirm a round.
+    pub min_job_results: u32,
+    pub min_update_delay_seconds: u32,
+    pub start_after: i64,
+    pub variance_threshold: SwitchboardDecimal,
+    pub force_report_period: i64,
+    pub expiration: i64,
+    pub consecutive_failure_count: u64,
+    pub next_allowed_update_time: i64,
+    pub is_locked: u8, // Bool.
+    pub crank_pubkey: [u8; 32],
+    pub latest_confirmed_round: AggregatorRound,
+}
+
+impl AggregatorAccountData {
+    pub fn load(aggregator: &AccountInfo) -> Result<AggregatorAccountData> {
+        let data = aggregator.try_borrow_data()?;
+        let end = DISCRIMINATOR_SIZE + std::mem::size_of::<AggregatorAccountData>();
+        if data.len() < end || data[..DISCRIMINATOR_SIZE] != SWITCHBOARD_AGGREGATOR_DISCRIMINATOR {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidOracleAccount)));
+        }
+
+        try_from_bytes::<AggregatorAccountData>(&data[DISCRIMINATOR_SIZE..end])
+            .map(|aggregator| *aggregator)
+            .map_err(|_| error!(FuzeErrorCode::InvalidOracleAccount))
+    }
+}
+
+#[cfg(target_endian = "little")]
+unsafe impl Zeroable for SwitchboardDecimal {}
+
+#[cfg(target_endian = "little")]
+unsafe impl Pod for SwitchboardDecimal {}
+
+#[cfg(target_endian = "little")]
+unsafe impl Zeroable for AggregatorRound {}
+
+#[cfg(target_endian = "little")]
+unsafe impl Pod for AggregatorRound {}
+
+#[cfg(target_endian = "little")]
+unsafe impl Zeroable for AggregatorAccountData {}
+
+#[cfg(target_endian = "little")]
+unsafe impl Pod for AggregatorAccountData {}
@@ -0,0 +1,770 @@
+use crate::*;
+use std::convert::TryInto;
+
+#[zero_copy]
+#[repr(packed)]
+pub struct Fill {
+    pub timestamp: i64,   // 8
+    pub price: u64,       // 8
+    pub size: u64,        // 8
+    pub fee: u64,         // 8
+    pub market_index: u8, // 1
+    pub side: Side,       // 1
+} // 34
+
+#[zero_copy]
+#[derive(Default)]
+#[repr(packed)]
+pub struct CostBasis {
+    pub size: i64,           // 8
+    pub cost_of_trades: u64, // 8
+    pub realized_pnl: i64,   // 8
+    pub fees: u64,           // 8
+} // 32
+
+impl CostBasis {
+    pub fn position(&self) -> Position {
+        Position {
+            size: self.size,
+            cost_of_trades: self.cost_of_trades,
+        }
+    }
+
+    /// Average entry price of the open position (6.dp), zero when flat.
+    pub fn get_average_entry_price(&self) -> Result<u64> {
+        let size = self.position().size_abs();
+        if size == 0 {
+            return Ok(0);
+        }
+
+        (self.cost_of_trades as u128)
+            .checked_mul(POSITION_PRECISION_DENOMINATOR)
+            .ok_or_else(math_error!(MathOverflow))?
+            .checked_div(size as u128)
+            .ok_or_else(math_error!(DivisionByZero))?
+            .try_into()
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))
+    }
+
+    pub fn get_unrealized_pnl(&self, mark_price: u64) -> Result<i64> {
+        self.position().get_unrealized_pnl(mark_price)
+    }
+
+    /// Applies a fill of `size` lots at `price` that paid `fee`.
+    ///
+    /// Lots that reduce the position release their pro rata share of the cost
+    /// basis and realize the difference to the fill price. The remainder
+    /// opens (or flips) the position at `price`. Realized pnl is gross of
+    /// fees, which are accumulated in `fees`.
+    pub fn record_fill(&mut self, side: Side, price: u64, size: u64, fee: u64) -> Result<()> {
+        if side == Side::Uninitialized {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidSide)));
+        }
+
+        let position = self.position();
+        let closing_size = if position.check_open(side) {
+            0
+        } else {
+            size.min(position.size_abs())
+        };
+
+        if closing_size > 0 {
+            let released_cost: u64 = (self.cost_of_trades as u128)
+                .checked_mul(closing_size as u128)
+                .ok_or_else(math_error!(MathOverflow))?
+                .checked_div(position.size_abs() as u128)
+                .ok_or_else(math_error!(DivisionByZero))?
+                .try_into()
+                .map_err(|_| error!(FuzeErrorCode::MathOverflow))?;
+            let proceeds = to_signed(get_trade_value(price, closing_size)?)?;
+            let realized_pnl = if self.size > 0 {
+                proceeds.checked_sub(to_signed(released_cost)?)
+            } else {
+                to_signed(released_cost)?.checked_sub(proceeds)
+            }
+            .ok_or_else(math_error!(MathUnderflow))?;
+
+            self.realized_pnl = self
+                .realized_pnl
+                .checked_add(realized_pnl)
+                .ok_or_else(math_error!(MathOverflow))?;
+            self.cost_of_trades = self
+                .cost_of_trades
+                .checked_sub(released_cost)
+                .ok_or_else(math_error!(MathUnderflow))?;
+        }
+
+        let opening_size = size
+            .checked_sub(closing_size)
+            .ok_or_else(math_error!(MathUnderflow))?;
+        if opening_size > 0 {
+            self.cost_of_trades = self
+                .cost_of_trades
+                .checked_add(get_trade_value(price, opening_size)?)
+                .ok_or_else(math_error!(MathOverflow))?;
+        }
+
+        let signed_size = to_signed(size)?;
+        self.size = match side {
+            Side::Bid => self.size.checked_add(signed_size),
+            _ => self.size.checked_sub(signed_size),
+        }
+        .ok_or_else(math_error!(MathOverflow))?;
+        self.fees = self
+            .fees
+            .checked_add(fee)
+            .ok_or_else(math_error!(MathOverflow))?;
+
+        Ok(())
+    }
+}
+
+/// Companion account of a margin account recording the fills of orders
+/// routed through zeta-cpi, with the cost basis and realized pnl per market
+/// index. PDA of `[TRADE_LEDGER_SEED, margin_account]`.
+#[account(zero_copy)]
+#[repr(packed)]
+pub struct TradeLedger {
+    pub nonce: u8,                               // 1
+    pub authority: Pubkey,                       // 32
+    pub margin_account: Pubkey,                  // 32
+    pub series_expiry: [u64; ACTIVE_EXPIRIES],   // 16
+    pub num_fills: u64,                          // 8
+    pub cost_bases: [CostBasis; ACTIVE_MARKETS], // 46 * 32 = 1472
+    pub fills: [Fill; TRADE_LEDGER_FILLS],       // 64 * 34 = 2176
+} // 3737
+
+impl TradeLedger {
+    pub fn get_cost_bases_slice(&self, expiry_index: usize) -> &[CostBasis] {
+        let head = expiry_index * NUM_PRODUCTS_PER_SERIES;
+        &self.cost_bases[head..head + NUM_PRODUCTS_PER_SERIES]
+    }
+
+    pub fn get_cost_bases_slice_mut(&mut self, expiry_index: usize) -> &mut [CostBasis] {
+        let head = expiry_index * NUM_PRODUCTS_PER_SERIES;
+        &mut self.cost_bases[head..head + NUM_PRODUCTS_PER_SERIES]
+    }
+
+    /// Returns the retained fills, oldest first.
+    pub fn get_fills(&self) -> Vec<Fill> {
+        let num_fills = self.num_fills as usize;
+        if num_fills <= TRADE_LEDGER_FILLS {
+            return self.fills[..num_fills].to_vec();
+        }
+
+        let head = num_fills % TRADE_LEDGER_FILLS;
+        let mut fills = self.fills[head..].to_vec();
+        fills.extend_from_slice(&self.fills[..head]);
+        fills
+    }
+
+    pub fn get_realized_pnl(&self) -> Result<i64> {
+        self.cost_bases.iter().try_fold(0i64, |total, cost_basis| {
+            total
+                .checked_add(cost_basis.realized_pnl)
+                .ok_or_else(math_error!(MathOverflow))
+        })
+    }
+
+    pub fn get_fees(&self) -> Result<u64> {
+        self.cost_bases.iter().try_fold(0u64, |total, cost_basis| {
+            total
+                .checked_add(cost_basis.fees)
+                .ok_or_else(math_error!(MathOverflow))
+        })
+    }
+
+    /// Market indexes are reused once a series expires. Zeta settles and
+    /// clears the product ledgers of an expired series, so its cost bases are
+    /// reset here when the zeta group has moved to a new expiry.
+    pub fn roll_expiries(&mut self, zeta_group: &ZetaGroup) {
+        for expiry_index in 0..ACTIVE_EXPIRIES {
+            let expiry_ts = zeta_group.expiry_series[expiry_index].expiry_ts;
+            if self.series_expiry[expiry_index] == expiry_ts {
+                continue;
+            }
+
+            for cost_basis in self.get_cost_bases_slice_mut(expiry_index) {
+                *cost_basis = CostBasis::default();
+            }
+            self.series_expiry[expiry_index] = expiry_ts;
+        }
+    }
+
+    pub fn record_fill(
+        &mut self,
+        market_index: usize,
+        side: Side,
+        price: u64,
+        size: u64,
+        fee: u64,
+        timestamp: i64,
+    ) -> Result<()> {
+        if market_index >= ACTIVE_MARKETS {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidMarketIndex)));
+        }
+
+        self.cost_bases[market_index].record_fill(side, price, size, fee)?;
+        self.fills[self.num_fills as usize % TRADE_LEDGER_FILLS] = Fill {
+            timestamp,
+            price,
+            size,
+            fee,
+            market_index: market_index as u8,
+            side,
+        };
+        self.num_fills = self
+            .num_fills
+            .checked_add(1)
+            .ok_or_else(math_error!(MathOverflow))?;
+
+        Ok(())
+    }
+
+    /// Position size held in the margin account but not recorded by the
+    /// ledger, e.g. fills of resting orders settled by the crank or trades
+    /// placed outside zeta-cpi.
+    pub fn get_unrecorded_size(
+        &self,
+        margin_account: &MarginAccount,
+        market_index: usize,
+    ) -> Result<i64> {
+        margin_account.product_ledgers[market_index]
+            .position
+            .size
+            .checked_sub(self.cost_bases[market_index].size)
+            .ok_or_else(math_error!(MathUnderflow))
+    }
+
+    /// Difference between the margin account's cost of trades and the
+    /// recorded cost basis. Non zero after unrecorded fills.
+    pub fn get_unrecorded_cost_of_trades(
+        &self,
+        margin_account: &MarginAccount,
+        market_index: usize,
+    ) -> Result<i64> {
+        to_signed(
+            margin_account.product_ledgers[market_index]
+                .position
+                .cost_of_trades,
+        )?
+        .checked_sub(to_signed(self.cost_bases[market_index].cost_of_trades)?)
+        .ok_or_else(math_error!(MathUnderflow))
+    }
+
+    /// Market indexes whose recorded size differs from the margin account.
+    pub fn get_unreconciled_market_indexes(
+        &self,
+        margin_account: &MarginAccount,
+    ) -> Result<Vec<usize>> {
+        let mut market_indexes = Vec::new();
+        for market_index in 0..ACTIVE_MARKETS {
+            if self.get_unrecorded_size(margin_account, market_index)? != 0 {
+                market_indexes.push(market_index);
+            }
+        }
+        Ok(market_indexes)
+    }
+
+    pub fn is_reconciled(&self, margin_account: &MarginAccount) -> Result<bool> {
+        Ok(self
+            .get_unreconciled_market_indexes(margin_account)?
+            .is_empty())
+    }
+}
+
+/// Value of `size` lots at `price` (6.dp).
+fn get_trade_value(price: u64, size: u64) -> Result<u64> {
+    (price as u128)
+        .checked_mul(size as u128)
+        .ok_or_else(math_error!(MathOverflow))?
+        .checked_div(POSITION_PRECISION_DENOMINATOR)
+        .ok_or_else(math_error!(DivisionByZero))?
+        .try_into()
+        .map_err(|_| error!(FuzeErrorCode::MathOverflow))
+}
+
+/// Margin account state read before and after a place order CPI.
+#[derive(Clone, Copy)]
+pub struct OrderSnapshot {
+    pub market_index: usize,
+    pub position: Position,
+    pub balance: u64,
+}
+
+/// Taker fee schedule of the order's market, used to separate the fee Zeta
+/// charges on a fill from the balance change.
+#[derive(Clone, Copy)]
+pub struct OrderFees {
+    pub rates: FeeRates,
+    pub kind: Kind,
+    pub spot_price: NativePrice,
+}
+
+impl OrderFees {
+    pub fn get_taker_fee(&self, price: u64, size: u64) -> Result<u64> {
+        get_trade_fee(
+            &self.rates,
+            self.kind,
+            NativePrice(price),
+            self.spot_price,
+            Lots(size),
+            Liquidity::Taker,
+        )
+        .map(|fee| fee.get())
+    }
+
+    /// Value of closing lots whose pnl net of the taker fee was `net_value`
+    /// minus the released cost (sells), or the released cost minus
+    /// `net_value` (buys). Option fees are charged on spot so they do not
+    /// depend on the value, future fees are a share of it.
+    fn get_closing_value(&self, side: Side, net_value: u64, size: u64) -> Result<u64> {
+        match self.kind {
+            Kind::Call | Kind::Put => {
+                // The price is unused, options pay on the spot notional.
+                let fee = self.get_taker_fee(0, size)?;
+                match side {
+                    Side::Ask => net_value
+                        .checked_add(fee)
+                        .ok_or_else(math_error!(MathOverflow)),
+                    _ => Ok(net_value.saturating_sub(fee)),
+                }
+            }
+            Kind::Future | Kind::Perp => {
+                let fee_percentage = self.rates.taker_trade_fee_percentage.get() as u128;
+                let denominator = match side {
+                    Side::Ask => NATIVE_PRECISION_DENOMINATOR.checked_sub(fee_percentage),
+                    _ => NATIVE_PRECISION_DENOMINATOR.checked_add(fee_percentage),
+                }
+                .ok_or_else(math_error!(MathUnderflow))?;
+                (net_value as u128)
+                    .checked_mul(NATIVE_PRECISION_DENOMINATOR)
+                    .ok_or_else(math_error!(MathOverflow))?
+                    .checked_div(denominator)
+                    .ok_or_else(math_error!(DivisionByZero))?
+                    .try_into()
+                    .map_err(|_| error!(FuzeErrorCode::MathOverflow))
+            }
+            Kind::Uninitialized => wrap_error!(Err(error!(FuzeErrorCode::UnsupportedKind))),
+        }
+    }
+}
+
+/// Records the taker fill of an order routed through zeta-cpi.
+///
+/// Zeta applies taker fills to the margin account within `place_order`, so
+/// the fill is the change in position across the CPI. Lots that open are
+/// priced exactly from the change in `cost_of_trades`. Lots that close
+/// release their pro rata share of the cost basis and settle the difference
+/// to the fill price into the balance, so their price is derived from the
+/// released cost and the balance change. Zeta also takes the fee of the
+/// whole order from the balance, so the fee of the opening lots is added
+/// back and the fee of the closing lots is solved for with `fees`, leaving
+/// the traded price.
+pub fn record_taker_fill(
+    trade_ledger: &mut TradeLedger,
+    fees: &OrderFees,
+    before: OrderSnapshot,
+    after: OrderSnapshot,
+    timestamp: i64,
+) -> Result<()> {
+    let size_change = after
+        .position
+        .size
+        .checked_sub(before.position.size)
+        .ok_or_else(math_error!(MathUnderflow))?;
+    if size_change == 0 {
+        return Ok(());
+    }
+
+    let side = if size_change > 0 {
+        Side::Bid
+    } else {
+        Side::Ask
+    };
+    let size = size_change.unsigned_abs();
+    let closing_size = if before.position.check_open(side) {
+        0
+    } else {
+        size.min(before.position.size_abs())
+    };
+    let opening_size = size
+        .checked_sub(closing_size)
+        .ok_or_else(math_error!(MathUnderflow))?;
+
+    let released_cost: u64 = if closing_size > 0 {
+        (before.position.cost_of_trades as u128)
+            .checked_mul(closing_size as u128)
+            .ok_or_else(math_error!(MathOverflow))?
+            .checked_div(before.position.size_abs() as u128)
+            .ok_or_else(math_error!(DivisionByZero))?
+            .try_into()
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))?
+    } else {
+        0
+    };
+
+    let mut opening_fill = None;
+    if opening_size > 0 {
+        let remaining_cost = before
+            .position
+            .cost_of_trades
+            .checked_sub(released_cost)
+            .ok_or_else(math_error!(MathUnderflow))?;
+        let opening_value = after
+            .position
+            .cost_of_trades
+            .checked_sub(remaining_cost)
+            .ok_or_else(math_error!(MathUnderflow))?;
+        let opening_price = get_fill_price(opening_value, opening_size)?;
+        opening_fill = Some((
+            opening_price,
+            fees.get_taker_fee(opening_price, opening_size)?,
+        ));
+    }
+
+    if closing_size > 0 {
+        let opening_fee = opening_fill.map_or(0, |(_, fee)| fee);
+        let balance_change = to_signed(after.balance)?
+            .checked_sub(to_signed(before.balance)?)
+            .ok_or_else(math_error!(MathUnderflow))?
+            .checked_add(to_signed(opening_fee)?)
+            .ok_or_else(math_error!(MathOverflow))?;
+        // Closing a long sells for the released cost plus the pnl, closing a
+        // short buys back for the released cost minus the pnl. Both net of
+        // the closing fee.
+        let net_value = match side {
+            Side::Ask => to_signed(released_cost)?.checked_add(balance_change),
+            _ => to_signed(released_cost)?.checked_sub(balance_change),
+        }
+        .ok_or_else(math_error!(MathOverflow))?
+        .max(0) as u64;
+        let closing_price = get_fill_price(
+            fees.get_closing_value(side, net_value, closing_size)?,
+            closing_size,
+        )?;
+
+        trade_ledger.record_fill(
+            before.market_index,
+            side,
+            closing_price,
+            closing_size,
+            fees.get_taker_fee(closing_price, closing_size)?,
+            timestamp,
+        )?;
+    }
+
+    if let Some((opening_price, opening_fee)) = opening_fill {
+        trade_ledger.record_fill(
+            before.market_index,
+            side,
+            opening_price,
+            opening_size,
+            opening_fee,
+            timestamp,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Price (6.dp) of a fill of `size` lots worth `value`, the inverse of
+/// `get_trade_value`.
+fn get_fill_price(value: u64, size: u64) -> Result<u64> {
+    (value as u128)
+        .checked_mul(POSITION_PRECISION_DENOMINATOR)
+        .ok_or_else(math_error!(MathOverflow))?
+        .checked_div(size as u128)
+        .ok_or_else(math_error!(DivisionByZero))?
+        .try_into()
+        .map_err(|_| error!(FuzeErrorCode::MathOverflow))
+}
+
+/// Returns the margin account's position in the order's market and its
+/// balance. Read before and after the place order CPI to find the fill.
+pub fn get_order_snapshot(accounts: &PlaceOrder) -> Result<OrderSnapshot> {
+    let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&accounts.zeta_group)?;
+    let market_index =
+        zeta_group.get_product_index_by_key(&accounts.market_accounts.market.key())?;
+    if market_index >= ACTIVE_MARKETS {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidMarketIndex)));
+    }
+
+    let margin_account =
+        deserialize_account_info_zerocopy::<MarginAccount>(&accounts.margin_account)?;
+    Ok(OrderSnapshot {
+        market_index,
+        position: margin_account.product_ledgers[market_index].position,
+        balance: margin_account.balance,
+    })
+}
+
+/// Returns the taker fee schedule of the order's market. Orders are placed
+/// without a whitelist trading fees account, so they pay the standard rates.
+pub fn get_order_fees(accounts: &PlaceOrder, market_index: usize) -> Result<OrderFees> {
+    let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&accounts.zeta_group)?;
+    let state = deserialize_account_info::<State>(&accounts.state)?;
+    Ok(OrderFees {
+        rates: FeeRates::from_state(&state, false),
+        kind: zeta_group.products[market_index].kind,
+        spot_price: get_native_oracle_price(
+            load_zeta_group_oracle_source(&zeta_group, &accounts.oracle)?.as_ref(),
+        )?,
+    })
+}
+
+/// Loads `trade_ledger`, which has to be the writable trade ledger of
+/// `margin_account`.
+pub fn load_trade_ledger<'info>(
+    trade_ledger: &AccountInfo<'info>,
+    margin_account: &Pubkey,
+) -> Result<AccountLoader<'info, TradeLedger>> {
+    let trade_ledger = AccountLoader::<TradeLedger>::try_from(trade_ledger)?;
+    if !trade_ledger.to_account_info().is_writable
+        || trade_ledger.load()?.margin_account != *margin_account
+    {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidTradeLedger)));
+    }
+    Ok(trade_ledger)
+}
+
+/// Runs `place_order`, the place order CPI for `accounts`, and records its
+/// taker fill when the margin account's `trade_ledger` is passed. Recording
+/// is opt in so the order instructions keep zeta's account lists.
+pub fn place_and_record_order<'info>(
+    accounts: &PlaceOrder<'info>,
+    trade_ledger: Option<&AccountInfo<'info>>,
+    place_order: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let trade_ledger = match trade_ledger {
+        Some(trade_ledger) => load_trade_ledger(trade_ledger, &accounts.margin_account.key())?,
+        None => return place_order(),
+    };
+
+    let before = get_order_snapshot(accounts)?;
+    let fees = get_order_fees(accounts, before.market_index)?;
+    place_order()?;
+
+    let mut trade_ledger = trade_ledger.load_mut()?;
+    {
+        let zeta_group = deserialize_account_info_zerocopy::<ZetaGroup>(&accounts.zeta_group)?;
+        trade_ledger.roll_expiries(&zeta_group);
+    }
+    record_taker_fill(
+        &mut trade_ledger,
+        &fees,
+        before,
+        get_order_snapshot(accounts)?,
+        Clock::get()?.unix_timestamp,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    fn snapshot(size: i64, cost_of_trades: u64, balance: u64) -> OrderSnapshot {
+        OrderSnapshot {
+            market_index: 3,
+            position: Position {
+                size,
+                cost_of_trades,
+            },
+            balance,
+        }
+    }
+
+    fn recorded_fills(trade_ledger: &TradeLedger) -> Vec<(Side, u64, u64, u64)> {
+        trade_ledger
+            .get_fills()
+            .iter()
+            .map(|fill| (fill.side, fill.price, fill.size, fill.fee))
+            .collect()
+    }
+
+    fn order_fees(kind: Kind, fee_percentage: u64) -> OrderFees {
+        OrderFees {
+            rates: FeeRates {
+                taker_trade_fee_percentage: Pct(fee_percentage),
+                taker_underlying_fee_percentage: Pct(fee_percentage),
+                maker_fee_percentage: Pct::ZERO,
+            },
+            kind,
+            spot_price: NativePrice(100_000_000),
+        }
+    }
+
+    #[test]
+    fn test_record_taker_fill_opening() {
+        let mut trade_ledger = TradeLedger::zeroed();
+        record_taker_fill(
+            &mut trade_ledger,
+            &order_fees(Kind::Future, 100_000),
+            snapshot(0, 0, 100_000_000),
+            snapshot(2_000, 10_000_000, 99_990_000),
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(
+            recorded_fills(&trade_ledger),
+            vec![(Side::Bid, 5_000_000, 2_000, 10_000)]
+        );
+        let cost_basis = trade_ledger.cost_bases[3];
+        assert_eq!({ cost_basis.size }, 2_000);
+        assert_eq!({ cost_basis.cost_of_trades }, 10_000_000);
+        assert_eq!({ cost_basis.fees }, 10_000);
+    }
+
+    #[test]
+    fn test_record_taker_fill_closing() {
+        let mut trade_ledger = TradeLedger::zeroed();
+        trade_ledger
+            .record_fill(3, Side::Bid, 5_000_000, 2_000, 0, 0)
+            .unwrap();
+
+        // Half the long is sold at 6, crediting 1 of pnl to the balance.
+        record_taker_fill(
+            &mut trade_ledger,
+            &order_fees(Kind::Future, 0),
+            snapshot(2_000, 10_000_000, 100_000_000),
+            snapshot(1_000, 5_000_000, 101_000_000),
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(
+            recorded_fills(&trade_ledger)[1..],
+            [(Side::Ask, 6_000_000, 1_000, 0)]
+        );
+        let cost_basis = trade_ledger.cost_bases[3];
+        assert_eq!({ cost_basis.cost_of_trades }, 5_000_000);
+        assert_eq!({ cost_basis.realized_pnl }, 1_000_000);
+
+        // The short side is bought back below its entry.
+        let mut trade_ledger = TradeLedger::zeroed();
+        record_taker_fill(
+            &mut trade_ledger,
+            &order_fees(Kind::Future, 0),
+            snapshot(-1_000, 5_000_000, 100_000_000),
+            snapshot(0, 0, 101_000_000),
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            recorded_fills(&trade_ledger),
+            vec![(Side::Bid, 4_000_000, 1_000, 0)]
+        );
+    }
+
+    #[test]
+    fn test_record_taker_fill_flip() {
+        let mut trade_ledger = TradeLedger::zeroed();
+        trade_ledger
+            .record_fill(3, Side::Bid, 5_000_000, 1_000, 0, 0)
+            .unwrap();
+
+        // Sells 3 lots at 4, closing the long at a loss of 1 and opening 2 short.
+        record_taker_fill(
+            &mut trade_ledger,
+            &order_fees(Kind::Future, 0),
+            snapshot(1_000, 5_000_000, 100_000_000),
+            snapshot(-2_000, 8_000_000, 99_000_000),
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(
+            recorded_fills(&trade_ledger)[1..],
+            [
+                (Side::Ask, 4_000_000, 1_000, 0),
+                (Side::Ask, 4_000_000, 2_000, 0)
+            ]
+        );
+        let cost_basis = trade_ledger.cost_bases[3];
+        assert_eq!({ cost_basis.size }, -2_000);
+        assert_eq!({ cost_basis.cost_of_trades }, 8_000_000);
+        assert_eq!({ cost_basis.realized_pnl }, -1_000_000);
+    }
+
+    #[test]
+    fn test_record_taker_fill_with_fees() {
+        let mut trade_ledger = TradeLedger::zeroed();
+        trade_ledger
+            .record_fill(3, Side::Bid, 5_000_000, 2_000, 0, 0)
+            .unwrap();
+
+        // Sells 1 lot at 6, the balance is credited 1 of pnl less the 0.1% fee.
+        record_taker_fill(
+            &mut trade_ledger,
+            &order_fees(Kind::Future, 100_000),
+            snapshot(2_000, 10_000_000, 100_000_000),
+            snapshot(1_000, 5_000_000, 100_994_000),
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            recorded_fills(&trade_ledger)[1..],
+            [(Side::Ask, 6_000_000, 1_000, 6_000)]
+        );
+
+        // Sells 3 lots at 4, closing the long at a loss of 1 and opening 2
+        // short. The fee of the whole order is taken from the balance.
+        record_taker_fill(
+            &mut trade_ledger,
+            &order_fees(Kind::Future, 100_000),
+            snapshot(1_000, 5_000_000, 100_994_000),
+            snapshot(-2_000, 8_000_000, 99_982_000),
+            2,
+        )
+        .unwrap();
+        assert_eq!(
+            recorded_fills(&trade_ledger)[2..],
+            [
+                (Side::Ask, 4_000_000, 1_000, 4_000),
+                (Side::Ask, 4_000_000, 2_000, 8_000)
+            ]
+        );
+        let cost_basis = trade_ledger.cost_bases[3];
+        assert_eq!({ cost_basis.size }, -2_000);
+        assert_eq!({ cost_basis.cost_of_trades }, 8_000_000);
+        assert_eq!({ cost_basis.realized_pnl }, 0);
+        assert_eq!({ cost_basis.fees }, 18_000);
+
+        // Option fees are charged on spot, 0.05% of 100 per lot. The short is
+        // bought back at 4, 1 below its entry.
+        let mut trade_ledger = TradeLedger::zeroed();
+        trade_ledger
+            .record_fill(3, Side::Ask, 5_000_000, 1_000, 0, 0)
+            .unwrap();
+        record_taker_fill(
+            &mut trade_ledger,
+            &order_fees(Kind::Call, 50_000),
+            snapshot(-1_000, 5_000_000, 100_000_000),
+            snapshot(0, 0, 100_950_000),
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            recorded_fills(&trade_ledger)[1..],
+            [(Side::Bid, 4_000_000, 1_000, 50_000)]
+        );
+        assert_eq!(trade_ledger.get_realized_pnl().unwrap(), 1_000_000);
+        assert_eq!(trade_ledger.get_fees().unwrap(), 50_000);
+    }
+
+    #[test]
+    fn test_record_taker_fill_unfilled() {
+        let mut trade_ledger = TradeLedger::zeroed();
+        record_taker_fill(
+            &mut trade_ledger,
+            &order_fees(Kind::Future, 0),
+            snapshot(1_000, 5_000_000, 100_000_000),
+            snapshot(1_000, 5_000_000, 100_000_000),
+            1,
+        )
+        .unwrap();
+        assert_eq!({ trade_ledger.num_fills }, 0);
+    }
+}
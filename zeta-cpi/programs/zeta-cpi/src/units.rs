@@ -0,0 +1,225 @@
+use crate::*;
+use std::convert::TryFrom;
+
+// Fixed point units of Zeta amounts. Accounts keep storing bare integers, the
+// newtypes are for the APIs taking or returning them so units can't be mixed.
+
+macro_rules! fixed_point_unit {
+    ($(#[$doc:meta])* $name:ident, $precision:expr) => {
+        $(#[$doc])*
+        #[derive(
+            AnchorSerialize,
+            AnchorDeserialize,
+            Clone,
+            Copy,
+            Debug,
+            Default,
+            PartialEq,
+            Eq,
+            PartialOrd,
+            Ord,
+            Hash,
+        )]
+        pub struct $name(pub u64);
+
+        impl $name {
+            pub const ZERO: $name = $name(0);
+            pub const PRECISION: u32 = $precision;
+
+            pub fn new(amount: u64) -> Self {
+                Self(amount)
+            }
+
+            pub fn get(self) -> u64 {
+                self.0
+            }
+
+            pub fn checked_add(self, other: Self) -> Result<Self> {
+                self.0
+                    .checked_add(other.0)
+                    .map(Self)
+                    .ok_or_else(math_error!(MathOverflow))
+            }
+
+            pub fn checked_sub(self, other: Self) -> Result<Self> {
+                self.0
+                    .checked_sub(other.0)
+                    .map(Self)
+                    .ok_or_else(math_error!(MathUnderflow))
+            }
+
+            pub fn saturating_sub(self, other: Self) -> Self {
+                Self(self.0.saturating_sub(other.0))
+            }
+
+            pub fn to_decimal(self) -> Decimal {
+                Decimal::from_i128_with_scale(self.0 as i128, Self::PRECISION)
+            }
+
+            /// Rounds to the nearest unit (ties to even), negative amounts underflow.
+            pub fn from_decimal(amount: Decimal) -> Result<Self> {
+                if amount.is_sign_negative() && !amount.is_zero() {
+                    return wrap_error!(Err(error!(FuzeErrorCode::MathUnderflow)));
+                }
+                amount
+                    .checked_mul(Decimal::from(10u64.pow(Self::PRECISION)))
+                    .ok_or_else(math_error!(MathOverflow))?
+                    .round()
+                    .to_u64()
+                    .map(Self)
+                    .ok_or_else(math_error!(MathOverflow))
+            }
+        }
+    };
+}
+
+fixed_point_unit!(
+    /// Native USDC amount (6.dp): prices, notionals, balances and margin.
+    NativePrice,
+    PLATFORM_PRECISION
+);
+
+fixed_point_unit!(
+    /// Position or order size in lots, `POSITION_PRECISION` (3.dp).
+    Lots,
+    POSITION_PRECISION
+);
+
+fixed_point_unit!(
+    /// Unsigned greek at `PRICING_PRECISION` (12.dp), e.g. delta or volatility.
+    PricingDecimal,
+    PRICING_PRECISION
+);
+
+fixed_point_unit!(
+    /// Percentage where `NATIVE_PRECISION_DENOMINATOR` is 100%, its decimal is
+    /// the fraction, i.e. 100% converts to 1.
+    Pct,
+    PCT_PRECISION
+);
+
+impl NativePrice {
+    /// Notional of `size` lots at this price.
+    pub fn checked_mul_lots(self, size: Lots) -> Result<NativePrice> {
+        let notional = (self.0 as u128)
+            .checked_mul(size.0.into())
+            .ok_or_else(math_error!(MathOverflow))?
+            .checked_div(POSITION_PRECISION_DENOMINATOR)
+            .ok_or_else(math_error!(DivisionByZero))?;
+        u64::try_from(notional)
+            .map(NativePrice)
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))
+    }
+
+    /// `pct` of this amount, rounded down.
+    pub fn checked_mul_pct(self, pct: Pct) -> Result<NativePrice> {
+        let amount = (self.0 as u128)
+            .checked_mul(pct.0.into())
+            .ok_or_else(math_error!(MathOverflow))?
+            .checked_div(NATIVE_PRECISION_DENOMINATOR)
+            .ok_or_else(math_error!(DivisionByZero))?;
+        u64::try_from(amount)
+            .map(NativePrice)
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))
+    }
+}
+
+impl Pct {
+    /// `numerator` as a percentage of `denominator`, rounded down.
+    pub fn from_ratio(numerator: NativePrice, denominator: NativePrice) -> Result<Pct> {
+        let pct = (numerator.0 as u128)
+            .checked_mul(NATIVE_PRECISION_DENOMINATOR)
+            .ok_or_else(math_error!(MathOverflow))?
+            .checked_div(denominator.0.into())
+            .ok_or_else(math_error!(DivisionByZero))?;
+        u64::try_from(pct)
+            .map(Pct)
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_mul_lots() {
+        // 2.5 lots at 40.123456, rounded down to the native unit.
+        assert_eq!(
+            NativePrice(40_123_456)
+                .checked_mul_lots(Lots(2_500))
+                .unwrap(),
+            NativePrice(100_308_640)
+        );
+        assert_eq!(
+            NativePrice(3).checked_mul_lots(Lots(333)).unwrap(),
+            NativePrice(0)
+        );
+        assert!(NativePrice(u64::MAX).checked_mul_lots(Lots(2_000)).is_err());
+    }
+
+    #[test]
+    fn test_checked_mul_pct() {
+        // 0.05% of 1234.567891.
+        assert_eq!(
+            NativePrice(1_234_567_891)
+                .checked_mul_pct(Pct(50_000))
+                .unwrap(),
+            NativePrice(617_283)
+        );
+        assert_eq!(
+            NativePrice(u64::MAX)
+                .checked_mul_pct(Pct(NATIVE_PRECISION_DENOMINATOR as u64))
+                .unwrap(),
+            NativePrice(u64::MAX)
+        );
+        assert!(NativePrice(u64::MAX)
+            .checked_mul_pct(Pct(2 * NATIVE_PRECISION_DENOMINATOR as u64))
+            .is_err());
+    }
+
+    #[test]
+    fn test_pct_from_ratio() {
+        assert_eq!(
+            Pct::from_ratio(NativePrice(1), NativePrice(3)).unwrap(),
+            Pct(33_333_333)
+        );
+        assert_eq!(
+            Pct::from_ratio(NativePrice(5), NativePrice(2))
+                .unwrap()
+                .to_decimal(),
+            Decimal::new(25, 1)
+        );
+        assert!(Pct::from_ratio(NativePrice(1), NativePrice::ZERO).is_err());
+    }
+
+    #[test]
+    fn test_from_decimal() {
+        assert_eq!(
+            NativePrice::from_decimal(Decimal::new(12_3456786, 7)).unwrap(),
+            NativePrice(12_345_679)
+        );
+        assert_eq!(
+            Lots::from_decimal(Decimal::new(1_0004, 4)).unwrap(),
+            Lots(1_000)
+        );
+        // Ties round to even.
+        assert_eq!(
+            Lots::from_decimal(Decimal::new(1_0005, 4)).unwrap(),
+            Lots(1_000)
+        );
+        assert_eq!(
+            Lots::from_decimal(Decimal::new(1_0015, 4)).unwrap(),
+            Lots(1_002)
+        );
+        assert_eq!(
+            Pct::from_decimal(Decimal::new(5, 2)).unwrap(),
+            Pct(5_000_000)
+        );
+        assert_eq!(
+            NativePrice::from_decimal(Decimal::new(-1, 8)).unwrap_err(),
+            error!(FuzeErrorCode::MathUnderflow)
+        );
+        assert!(Lots::from_decimal(Decimal::from(u64::MAX)).is_err());
+    }
+}
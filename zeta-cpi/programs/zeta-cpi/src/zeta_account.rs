@@ -11,6 +11,12 @@ pub struct ProductGreeks {
     pub volatility: AnchorDecimal,
 } // 40
 
+impl ProductGreeks {
+    pub fn get_delta(&self) -> PricingDecimal {
+        PricingDecimal(self.delta)
+    }
+}
+
 #[zero_copy]
 #[derive(Default)]
 #[repr(packed)]
@@ -49,6 +55,49 @@ unsafe impl Zeroable for AnchorDecimal {}
 #[cfg(target_endian = "little")]
 unsafe impl Pod for AnchorDecimal {}
 
+// Zeta's borsh serialized global state, read with `deserialize_account_info`.
+// Only the leading fields are mirrored, the remainder of the account is ignored.
+#[account]
+#[derive(Default)]
+pub struct State {
+    pub admin: Pubkey,                                   // 32
+    pub state_nonce: u8,                                 // 1
+    pub serum_nonce: u8,                                 // 1
+    pub mint_auth_nonce: u8,                             // 1
+    pub num_underlyings: u8,                             // 1
+    pub expiry_interval_seconds: u32,                    // 4
+    pub new_expiry_threshold_seconds: u32,               // 4
+    pub strike_initialization_threshold_seconds: u32,    // 4
+    pub pricing_frequency_seconds: u32,                  // 4
+    pub liquidator_liquidation_percentage: u32,          // 4
+    pub insurance_vault_liquidation_percentage: u32,     // 4
+    pub native_trade_fee_percentage: u64,                // 8
+    pub native_underlying_fee_percentage: u64,           // 8
+    pub native_whitelist_underlying_fee_percentage: u64, // 8
+    pub native_deposit_limit: u64,                       // 8
+    pub expiration_threshold_seconds: u32,               // 4
+    pub position_movement_fee_bps: u8,                   // 1
+    pub margin_concession_percentage: u8,                // 1
+}
+
+// Share of the zeta group's insurance vault held by `authority`, out of
+// `ZetaGroup::total_insurance_vault_deposits`.
+#[account]
+#[derive(Default)]
+pub struct InsuranceDepositAccount {
+    pub nonce: u8,         // 1
+    pub amount: u64,       // 8
+    pub authority: Pubkey, // 32
+}
+
+// Insurance deposits are restricted to whitelisted users.
+#[account]
+#[derive(Default)]
+pub struct WhitelistInsuranceAccount {
+    pub nonce: u8,        // 1
+    pub user_key: Pubkey, // 32
+}
+
 #[account(zero_copy)]
 #[repr(packed)]
 pub struct Greeks {
@@ -356,30 +405,28 @@ impl Position {
         self.size.abs() as u64
     }
 
-    pub fn get_unrealized_pnl(&self, mark_price: u64) -> i64 {
+    pub fn get_unrealized_pnl(&self, mark_price: u64) -> Result<i64> {
         if self.size == 0 {
-            0
-        } else if self.size > 0 {
-            (self.size as i128)
-                .checked_mul(mark_price as i128)
-                .unwrap()
-                .checked_div(POSITION_PRECISION_DENOMINATOR as i128)
-                .unwrap()
+            return Ok(0);
+        }
+
+        let position_value = (self.size as i128)
+            .checked_mul(mark_price as i128)
+            .ok_or_else(math_error!(MathOverflow))?
+            .checked_div(POSITION_PRECISION_DENOMINATOR as i128)
+            .ok_or_else(math_error!(DivisionByZero))?;
+        let unrealized_pnl = if self.size > 0 {
+            position_value
                 .checked_sub(self.cost_of_trades as i128)
-                .unwrap()
-                .try_into()
-                .unwrap()
+                .ok_or_else(math_error!(MathUnderflow))?
         } else {
-            (self.size as i128)
-                .checked_mul(mark_price as i128)
-                .unwrap()
-                .checked_div(POSITION_PRECISION_DENOMINATOR as i128)
-                .unwrap()
+            position_value
                 .checked_add(self.cost_of_trades as i128)
-                .unwrap()
-                .try_into()
-                .unwrap()
-        }
+                .ok_or_else(math_error!(MathOverflow))?
+        };
+        unrealized_pnl
+            .try_into()
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))
     }
 }
 
@@ -416,24 +463,63 @@ impl ProductLedger {
         self.position.empty() && !self.order_state.has_active_orders()
     }
 
+    /// Returns a copy of the ledger with an order of `size` lots added to its
+    /// order state. Lots that reduce the position are tracked as closing
+    /// orders, the remainder as opening orders on `side`.
+    pub fn with_order(&self, side: Side, size: u64) -> Result<ProductLedger> {
+        let side_index = match side {
+            Side::Bid => BID_ORDERS_INDEX,
+            Side::Ask => ASK_ORDERS_INDEX,
+            Side::Uninitialized => return wrap_error!(Err(error!(FuzeErrorCode::InvalidSide))),
+        };
+
+        let closing_size = if self.position.check_open(side) {
+            0
+        } else {
+            self.position
+                .size_abs()
+                .saturating_sub(self.order_state.closing_orders)
+                .min(size)
+        };
+        let opening_size = size
+            .checked_sub(closing_size)
+            .ok_or_else(math_error!(MathUnderflow))?;
+
+        let mut ledger = *self;
+        ledger.order_state.closing_orders = ledger
+            .order_state
+            .closing_orders
+            .checked_add(closing_size)
+            .ok_or_else(math_error!(MathOverflow))?;
+        ledger.order_state.opening_orders[side_index] = ledger.order_state.opening_orders
+            [side_index]
+            .checked_add(opening_size)
+            .ok_or_else(math_error!(MathOverflow))?;
+        Ok(ledger)
+    }
+
     pub fn get_initial_margin(
         &self,
-        mark_price: u64,
+        mark_price: NativePrice,
         product: &Product,
-        spot: u64,
+        spot: NativePrice,
         margin_parameters: &MarginParameters,
-    ) -> u64 {
-        let strike: u64 = match product.strike.get_strike() {
-            Ok(strike) => strike,
-            Err(_) => return 0,
+    ) -> Result<NativePrice> {
+        let strike = match product.strike.get_strike() {
+            Ok(strike) => NativePrice(strike),
+            Err(_) => return Ok(NativePrice::ZERO),
         };
 
         let mut long_lots: u64 = self.order_state.opening_orders[BID_ORDERS_INDEX];
         let mut short_lots: u64 = self.order_state.opening_orders[ASK_ORDERS_INDEX];
         if self.position.size > 0 {
-            long_lots = long_lots.checked_add(self.position.size_abs()).unwrap();
+            long_lots = long_lots
+                .checked_add(self.position.size_abs())
+                .ok_or_else(math_error!(MathOverflow))?;
         } else if self.position.size < 0 {
-            short_lots = short_lots.checked_add(self.position.size_abs()).unwrap();
+            short_lots = short_lots
+                .checked_add(self.position.size_abs())
+                .ok_or_else(math_error!(MathOverflow))?;
         }
 
         let mut long_initial_margin: u128 = 0;
@@ -449,12 +535,10 @@ impl ProductLedger {
                         product.kind,
                         Side::Bid,
                         margin_parameters,
-                    )
-                    .unwrap()
-                    .try_into()
-                    .unwrap(),
+                    )?
+                    .get() as u128,
                 )
-                .unwrap();
+                .ok_or_else(math_error!(MathOverflow))?;
         }
 
         if short_lots > 0 {
@@ -467,53 +551,54 @@ impl ProductLedger {
                         product.kind,
                         Side::Ask,
                         margin_parameters,
-                    )
-                    .unwrap()
-                    .try_into()
-                    .unwrap(),
+                    )?
+                    .get() as u128,
                 )
-                .unwrap();
+                .ok_or_else(math_error!(MathOverflow))?;
         }
 
         if product.kind == Kind::Future {
             if long_lots > short_lots {
                 return long_initial_margin
                     .checked_div(POSITION_PRECISION_DENOMINATOR)
-                    .unwrap()
+                    .ok_or_else(math_error!(DivisionByZero))?
                     .try_into()
-                    .unwrap();
+                    .map(NativePrice)
+                    .map_err(|_| error!(FuzeErrorCode::MathOverflow));
             } else {
                 return short_initial_margin
                     .checked_div(POSITION_PRECISION_DENOMINATOR)
-                    .unwrap()
+                    .ok_or_else(math_error!(DivisionByZero))?
                     .try_into()
-                    .unwrap();
+                    .map(NativePrice)
+                    .map_err(|_| error!(FuzeErrorCode::MathOverflow));
             }
         }
 
         long_initial_margin
             .checked_add(short_initial_margin)
-            .unwrap()
+            .ok_or_else(math_error!(MathOverflow))?
             .checked_div(POSITION_PRECISION_DENOMINATOR)
-            .unwrap()
+            .ok_or_else(math_error!(DivisionByZero))?
             .try_into()
-            .unwrap()
+            .map(NativePrice)
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))
     }
 
     pub fn get_maintenance_margin(
         &self,
-        mark_price: u64,
+        mark_price: NativePrice,
         product: &Product,
-        spot: u64,
+        spot: NativePrice,
         margin_parameters: &MarginParameters,
-    ) -> u64 {
+    ) -> Result<NativePrice> {
         if self.position.size == 0 {
-            return 0;
+            return Ok(NativePrice::ZERO);
         }
 
-        let strike: u64 = match product.strike.get_strike() {
-            Ok(strike) => strike,
-            Err(_) => return 0,
+        let strike = match product.strike.get_strike() {
+            Ok(strike) => NativePrice(strike),
+            Err(_) => return Ok(NativePrice::ZERO),
         };
 
         let maintenance_margin_per_lot = get_maintenance_margin_per_lot(
@@ -523,26 +608,28 @@ impl ProductLedger {
             product.kind,
             self.position.size >= 0,
             margin_parameters,
-        )
-        .unwrap();
+        )?;
 
         (self.position.size_abs() as u128)
-            .checked_mul(maintenance_margin_per_lot as u128)
-            .unwrap()
+            .checked_mul(maintenance_margin_per_lot.get() as u128)
+            .ok_or_else(math_error!(MathOverflow))?
             .checked_div(POSITION_PRECISION_DENOMINATOR)
-            .unwrap() as u64
+            .ok_or_else(math_error!(DivisionByZero))?
+            .try_into()
+            .map(NativePrice)
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))
     }
 
     pub fn get_maintenance_margin_including_orders(
         &self,
-        mark_price: u64,
+        mark_price: NativePrice,
         product: &Product,
-        spot: u64,
+        spot: NativePrice,
         margin_parameters: &MarginParameters,
-    ) -> u64 {
-        let strike: u64 = match product.strike.get_strike() {
-            Ok(strike) => strike,
-            Err(_) => return 0,
+    ) -> Result<NativePrice> {
+        let strike = match product.strike.get_strike() {
+            Ok(strike) => NativePrice(strike),
+            Err(_) => return Ok(NativePrice::ZERO),
         };
 
         let mut long_lots: u128 = self.order_state.opening_orders[BID_ORDERS_INDEX].into();
@@ -551,15 +638,15 @@ impl ProductLedger {
         if self.position.size > 0 {
             long_lots = long_lots
                 .checked_add(self.position.size_abs() as u128)
-                .unwrap();
-        } else {
-            short_lots = long_lots
+                .ok_or_else(math_error!(MathOverflow))?;
+        } else if self.position.size < 0 {
+            short_lots = short_lots
                 .checked_add(self.position.size_abs() as u128)
-                .unwrap();
+                .ok_or_else(math_error!(MathOverflow))?;
         }
 
-        let mut maintenance_margin_long = 0;
-        let mut maintenance_margin_short = 0;
+        let mut maintenance_margin_long = NativePrice::ZERO;
+        let mut maintenance_margin_short = NativePrice::ZERO;
 
         if long_lots > 0 {
             maintenance_margin_long = long_lots
@@ -571,12 +658,15 @@ impl ProductLedger {
                         product.kind,
                         true,
                         margin_parameters,
-                    )
-                    .unwrap() as u128,
+                    )?
+                    .get() as u128,
                 )
-                .unwrap()
+                .ok_or_else(math_error!(MathOverflow))?
                 .checked_div(POSITION_PRECISION_DENOMINATOR)
-                .unwrap() as u64;
+                .ok_or_else(math_error!(DivisionByZero))?
+                .try_into()
+                .map(NativePrice)
+                .map_err(|_| error!(FuzeErrorCode::MathOverflow))?;
         }
 
         if short_lots > 0 {
@@ -589,30 +679,31 @@ impl ProductLedger {
                         product.kind,
                         false,
                         margin_parameters,
-                    )
-                    .unwrap() as u128,
+                    )?
+                    .get() as u128,
                 )
-                .unwrap()
+                .ok_or_else(math_error!(MathOverflow))?
                 .checked_div(POSITION_PRECISION_DENOMINATOR)
-                .unwrap() as u64;
+                .ok_or_else(math_error!(DivisionByZero))?
+                .try_into()
+                .map(NativePrice)
+                .map_err(|_| error!(FuzeErrorCode::MathOverflow))?;
         }
 
-        maintenance_margin_long
-            .checked_add(maintenance_margin_short)
-            .unwrap()
+        maintenance_margin_long.checked_add(maintenance_margin_short)
     }
 
     pub fn get_margin_market_maker_concession(
         &self,
-        mark_price: u64,
+        mark_price: NativePrice,
         product: &Product,
-        spot: u64,
+        spot: NativePrice,
         margin_parameters: &MarginParameters,
         concession_percentage: u8,
-    ) -> u64 {
-        let strike: u64 = match product.strike.get_strike() {
-            Ok(strike) => strike,
-            Err(_) => return 0,
+    ) -> Result<NativePrice> {
+        let strike = match product.strike.get_strike() {
+            Ok(strike) => NativePrice(strike),
+            Err(_) => return Ok(NativePrice::ZERO),
         };
 
         let long_lots: u64 = self.order_state.opening_orders[BID_ORDERS_INDEX];
@@ -630,12 +721,10 @@ impl ProductLedger {
                         product.kind,
                         Side::Bid,
                         margin_parameters,
-                    )
-                    .unwrap()
-                    .try_into()
-                    .unwrap(),
+                    )?
+                    .get() as u128,
                 )
-                .unwrap();
+                .ok_or_else(math_error!(MathOverflow))?;
         }
 
         if short_lots > 0 {
@@ -648,22 +737,20 @@ impl ProductLedger {
                         product.kind,
                         Side::Ask,
                         margin_parameters,
-                    )
-                    .unwrap()
-                    .try_into()
-                    .unwrap(),
+                    )?
+                    .get() as u128,
                 )
-                .unwrap();
+                .ok_or_else(math_error!(MathOverflow))?;
         }
 
         // Apply the concession
         let total_initial_margin = long_initial_margin
             .checked_add(short_initial_margin)
-            .unwrap()
+            .ok_or_else(math_error!(MathOverflow))?
             .checked_mul(concession_percentage.into())
-            .unwrap()
+            .ok_or_else(math_error!(MathOverflow))?
             .checked_div(100)
-            .unwrap();
+            .ok_or_else(math_error!(DivisionByZero))?;
 
         let maintenance_margin_per_lot = get_maintenance_margin_per_lot(
             spot,
@@ -672,21 +759,21 @@ impl ProductLedger {
             product.kind,
             self.position.size >= 0,
             margin_parameters,
-        )
-        .unwrap();
+        )?;
 
         // Normalize it with the initial_margin
         let maintenance_margin = (self.position.size_abs() as u128)
-            .checked_mul(maintenance_margin_per_lot as u128)
-            .unwrap();
+            .checked_mul(maintenance_margin_per_lot.get() as u128)
+            .ok_or_else(math_error!(MathOverflow))?;
 
         maintenance_margin
             .checked_add(total_initial_margin)
-            .unwrap()
+            .ok_or_else(math_error!(MathOverflow))?
             .checked_div(POSITION_PRECISION_DENOMINATOR)
-            .unwrap()
+            .ok_or_else(math_error!(DivisionByZero))?
             .try_into()
-            .unwrap()
+            .map(NativePrice)
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))
     }
 }
 
@@ -731,6 +818,94 @@ impl SpreadAccount {
             .find(|x| x.size != 0)
             .is_some()
     }
+
+    pub fn get_unrealized_pnl(&self, greeks: &Greeks) -> Result<i64> {
+        self.positions
+            .iter()
+            .enumerate()
+            .try_fold(0i64, |total, (i, position)| {
+                total
+                    .checked_add(position.get_unrealized_pnl(greeks.mark_prices[i])?)
+                    .ok_or_else(math_error!(MathOverflow))
+            })
+    }
+
+    /// Collateral required to hold the positions of an expiry series to expiry.
+    ///
+    /// Spread accounts only hold defined risk spreads, which are collateralised
+    /// by their max loss at expiry. The payoff is linear between strikes, so
+    /// the max loss is found at a strike or at a spot of zero. Above the highest
+    /// strike the slope is the net call and future size, which must not be
+    /// short or the loss is unbounded.
+    pub fn get_series_margin(
+        &self,
+        zeta_group: &ZetaGroup,
+        expiry_index: usize,
+    ) -> Result<NativePrice> {
+        let positions = self.get_positions_slice(expiry_index);
+        let products = zeta_group.get_products_slice(expiry_index);
+        if !self.has_position_in_expiry_index(expiry_index) {
+            return Ok(NativePrice::ZERO);
+        }
+
+        let upside_size: i64 = positions
+            .iter()
+            .zip(products)
+            .filter(|(_, product)| product.kind != Kind::Put)
+            .map(|(position, _)| position.size)
+            .sum();
+        if upside_size < 0 {
+            return wrap_error!(Err(error!(FuzeErrorCode::SpreadAccountUndefinedRisk)));
+        }
+
+        let mut max_loss: i64 = 0;
+        let settlement_prices = std::iter::once(0).chain(
+            products
+                .iter()
+                .filter_map(|product| product.strike.get_strike().ok()),
+        );
+        for settlement_price in settlement_prices {
+            let mut pnl: i64 = 0;
+            for (position, product) in positions.iter().zip(products) {
+                if position.size == 0 {
+                    continue;
+                }
+                let strike = match product.kind {
+                    Kind::Future | Kind::Perp => 0,
+                    _ => product.strike.get_strike()?,
+                };
+                let value = get_intrinsic_value(
+                    NativePrice(settlement_price),
+                    NativePrice(strike),
+                    product.kind,
+                )?;
+                pnl = pnl
+                    .checked_add(position.get_unrealized_pnl(value.get())?)
+                    .ok_or_else(math_error!(MathOverflow))?;
+            }
+            max_loss = max_loss.max(pnl.checked_neg().ok_or_else(math_error!(MathOverflow))?);
+        }
+
+        max_loss
+            .try_into()
+            .map(NativePrice)
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))
+    }
+
+    /// Collateral required across all expiry series.
+    pub fn get_margin(&self, zeta_group: &ZetaGroup) -> Result<NativePrice> {
+        let mut margin = NativePrice::ZERO;
+        for expiry_index in 0..ACTIVE_EXPIRIES {
+            margin = margin.checked_add(self.get_series_margin(zeta_group, expiry_index)?)?;
+        }
+        Ok(margin)
+    }
+
+    /// Balance above the collateral requirement, which
+    /// `transfer_excess_spread_balance` releases to the margin account.
+    pub fn get_excess_balance(&self, zeta_group: &ZetaGroup) -> Result<NativePrice> {
+        Ok(NativePrice(self.balance).saturating_sub(self.get_margin(zeta_group)?))
+    }
 }
 
 #[account(zero_copy)]
@@ -751,6 +926,25 @@ pub struct MarginAccount {
 } // 6144
 
 impl MarginAccount {
+    pub fn empty(&self) -> bool {
+        if self.has_positions() || self.has_active_orders() {
+            return false;
+        }
+        self.balance == 0
+    }
+
+    pub fn has_positions(&self) -> bool {
+        self.product_ledgers
+            .iter()
+            .any(|ledger| !ledger.position.empty())
+    }
+
+    pub fn has_active_orders(&self) -> bool {
+        self.product_ledgers
+            .iter()
+            .any(|ledger| ledger.order_state.has_active_orders())
+    }
+
     pub fn get_product_ledgers_slice_mut(&mut self, expiry_index: usize) -> &mut [ProductLedger] {
         let head = expiry_index * NUM_PRODUCTS_PER_SERIES;
         &mut self.product_ledgers[head..head + NUM_PRODUCTS_PER_SERIES]
@@ -762,22 +956,34 @@ impl MarginAccount {
     }
 
     // Calculates the total initial margin for all open orders and positions.
-    pub fn get_initial_margin(&self, greeks: &Greeks, zeta_group: &ZetaGroup, spot: u64) -> u64 {
-        let initial_margin_requirement = self
-            .product_ledgers
+    pub fn get_initial_margin(
+        &self,
+        greeks: &Greeks,
+        zeta_group: &ZetaGroup,
+        spot: NativePrice,
+    ) -> Result<NativePrice> {
+        let mark_prices = greeks.mark_prices;
+        self.get_initial_margin_for_marks(&mark_prices, zeta_group, spot)
+    }
+
+    // Same as `get_initial_margin` with the given mark prices instead of greeks.
+    pub fn get_initial_margin_for_marks(
+        &self,
+        mark_prices: &[u64],
+        zeta_group: &ZetaGroup,
+        spot: NativePrice,
+    ) -> Result<NativePrice> {
+        self.product_ledgers
             .iter()
             .enumerate()
-            .map(|(i, ledger)| {
-                ledger.get_initial_margin(
-                    greeks.mark_prices[i],
+            .try_fold(NativePrice::ZERO, |total, (i, ledger)| {
+                total.checked_add(ledger.get_initial_margin(
+                    NativePrice(mark_prices[i]),
                     &zeta_group.products[i],
                     spot,
                     &zeta_group.margin_parameters,
-                )
+                )?)
             })
-            .sum();
-
-        initial_margin_requirement
     }
 
     // Calculates the total maintenance margin for all positions only.
@@ -785,58 +991,369 @@ impl MarginAccount {
         &self,
         greeks: &Greeks,
         zeta_group: &ZetaGroup,
-        spot: u64,
-    ) -> u64 {
-        let maintenance_margin_requirement = self
-            .product_ledgers
-            .iter()
-            .enumerate()
-            .map(|(i, product_ledgers)| {
-                product_ledgers.get_maintenance_margin(
-                    greeks.mark_prices[i],
+        spot: NativePrice,
+    ) -> Result<NativePrice> {
+        let mark_prices = greeks.mark_prices;
+        self.get_maintenance_margin_for_marks(&mark_prices, zeta_group, spot)
+    }
+
+    // Same as `get_maintenance_margin` with the given mark prices instead of greeks.
+    pub fn get_maintenance_margin_for_marks(
+        &self,
+        mark_prices: &[u64],
+        zeta_group: &ZetaGroup,
+        spot: NativePrice,
+    ) -> Result<NativePrice> {
+        self.product_ledgers.iter().enumerate().try_fold(
+            NativePrice::ZERO,
+            |total, (i, product_ledger)| {
+                total.checked_add(product_ledger.get_maintenance_margin(
+                    NativePrice(mark_prices[i]),
                     &zeta_group.products[i],
                     spot,
                     &zeta_group.margin_parameters,
-                )
-            })
-            .sum();
+                )?)
+            },
+        )
+    }
 
-        maintenance_margin_requirement
+    pub fn get_unrealized_pnl(&self, greeks: &Greeks) -> Result<i64> {
+        let mark_prices = greeks.mark_prices;
+        self.get_unrealized_pnl_for_marks(&mark_prices)
     }
 
-    pub fn get_unrealized_pnl(&self, greeks: &Greeks) -> i64 {
+    pub fn get_unrealized_pnl_for_marks(&self, mark_prices: &[u64]) -> Result<i64> {
         self.product_ledgers
             .iter()
             .enumerate()
-            .map(|(i, product_ledger)| {
-                (product_ledger
-                    .position
-                    .get_unrealized_pnl(greeks.mark_prices[i]) as i128) as i64
+            .try_fold(0i64, |total, (i, product_ledger)| {
+                total
+                    .checked_add(product_ledger.position.get_unrealized_pnl(mark_prices[i])?)
+                    .ok_or_else(math_error!(MathOverflow))
             })
-            .sum()
     }
 
     pub fn get_maintenance_margin_including_orders(
         &self,
         greeks: &Greeks,
         zeta_group: &ZetaGroup,
-        spot: u64,
-    ) -> u64 {
-        let maintenance_margin_requirement = self
-            .product_ledgers
-            .iter()
-            .enumerate()
-            .map(|(i, product_ledger)| {
-                product_ledger.get_maintenance_margin_including_orders(
-                    greeks.mark_prices[i],
+        spot: NativePrice,
+    ) -> Result<NativePrice> {
+        self.product_ledgers.iter().enumerate().try_fold(
+            NativePrice::ZERO,
+            |total, (i, product_ledger)| {
+                total.checked_add(product_ledger.get_maintenance_margin_including_orders(
+                    NativePrice(greeks.mark_prices[i]),
                     &zeta_group.products[i],
                     spot,
                     &zeta_group.margin_parameters,
-                )
-            })
-            .sum();
+                )?)
+            },
+        )
+    }
+
+    /// Projects the margin account state after placing an order of `size`
+    /// lots at `price` on `market_index`.
+    ///
+    /// The order is added to the product ledger as `OrderState` would track
+    /// it, so lots that close an existing position add no initial margin.
+    /// If `price` crosses the mark price, the premium paid through the mark
+    /// is charged as an unrealized loss, as a fill would be marked down.
+    pub fn simulate_order(
+        &self,
+        market_index: usize,
+        side: Side,
+        price: NativePrice,
+        size: Lots,
+        greeks: &Greeks,
+        zeta_group: &ZetaGroup,
+        spot: NativePrice,
+        margin_concession_percentage: Option<u8>,
+    ) -> Result<MarginAccountState> {
+        if market_index >= self.product_ledgers.len() {
+            return wrap_error!(Err(error!(FuzeErrorCode::InvalidMarketIndex)));
+        }
+
+        let ledger = self.product_ledgers[market_index];
+        let simulated_ledger = ledger.with_order(side, size.get())?;
+        let mark_price = NativePrice(greeks.mark_prices[market_index]);
+        let product = &zeta_group.products[market_index];
+        let margin_parameters = &zeta_group.margin_parameters;
+
+        let state =
+            get_margin_account_state(zeta_group, self, greeks, spot, margin_concession_percentage)?;
+        let initial_margin = state
+            .initial_margin
+            .checked_sub(self.get_ledger_initial_margin(
+                &ledger,
+                mark_price,
+                product,
+                spot,
+                margin_parameters,
+                margin_concession_percentage,
+            )?)?
+            .checked_add(self.get_ledger_initial_margin(
+                &simulated_ledger,
+                mark_price,
+                product,
+                spot,
+                margin_parameters,
+                margin_concession_percentage,
+            )?)?;
+
+        let unrealized_pnl = state
+            .unrealized_pnl
+            .checked_sub(get_crossing_loss(side, price, size, mark_price)?)
+            .ok_or_else(math_error!(MathUnderflow))?;
+
+        MarginAccountState::new(
+            NativePrice(self.balance),
+            initial_margin,
+            state.maintenance_margin,
+            unrealized_pnl,
+        )
+    }
+
+    /// Same as `simulate_order` for a batch of orders resting together, each
+    /// order's crossing loss against mark is charged on top of the others.
+    pub fn simulate_orders(
+        &self,
+        orders: &[OrderSpec],
+        greeks: &Greeks,
+        zeta_group: &ZetaGroup,
+        spot: NativePrice,
+        margin_concession_percentage: Option<u8>,
+    ) -> Result<MarginAccountState> {
+        let mut simulated = *self;
+        let mut crossing_loss: i64 = 0;
+        for order in orders {
+            let market_index = usize::from(order.market_index);
+            if market_index >= simulated.product_ledgers.len() {
+                return wrap_error!(Err(error!(FuzeErrorCode::InvalidMarketIndex)));
+            }
+
+            simulated.product_ledgers[market_index] =
+                simulated.product_ledgers[market_index].with_order(order.side, order.size)?;
+            crossing_loss = crossing_loss
+                .checked_add(get_crossing_loss(
+                    order.side,
+                    NativePrice(order.price),
+                    Lots(order.size),
+                    NativePrice(greeks.mark_prices[market_index]),
+                )?)
+                .ok_or_else(math_error!(MathOverflow))?;
+        }
+
+        let state = get_margin_account_state(
+            zeta_group,
+            &simulated,
+            greeks,
+            spot,
+            margin_concession_percentage,
+        )?;
+        let unrealized_pnl = state
+            .unrealized_pnl
+            .checked_sub(crossing_loss)
+            .ok_or_else(math_error!(MathUnderflow))?;
+
+        MarginAccountState::new(
+            NativePrice(self.balance),
+            state.initial_margin,
+            state.maintenance_margin,
+            unrealized_pnl,
+        )
+    }
+
+    /// Initial margin of a single product ledger, under the requirement from
+    /// `get_initial_margin_requirement_type`.
+    pub fn get_ledger_initial_margin(
+        &self,
+        ledger: &ProductLedger,
+        mark_price: NativePrice,
+        product: &Product,
+        spot: NativePrice,
+        margin_parameters: &MarginParameters,
+        margin_concession_percentage: Option<u8>,
+    ) -> Result<NativePrice> {
+        match self.get_initial_margin_requirement_type() {
+            MarginRequirement::MarketMakerConcession => ledger.get_margin_market_maker_concession(
+                mark_price,
+                product,
+                spot,
+                margin_parameters,
+                self.validate_margin_concession(margin_concession_percentage)?,
+            ),
+            _ => ledger.get_initial_margin(mark_price, product, spot, margin_parameters),
+        }
+    }
+
+    /// Returns the largest order size in `POSITION_PRECISION` lots on
+    /// `market_index` that keeps `available_balance_initial >= 0`.
+    ///
+    /// Lots that close the existing position only pay for crossing the mark.
+    /// For futures, lots that net against the other side are also free, as
+    /// futures initial margin is taken on the larger side only. Remaining
+    /// lots cost `get_initial_margin_per_lot`, which applies the short put
+    /// cap of `option_short_put_cap_percentage`. Market maker accounts pay
+    /// the concession percentage of that and get no futures netting.
+    ///
+    /// Errors with `UnpricedMarket` when those lots cost nothing, e.g. an
+    /// option without a mark, as the size would be unbounded.
+    pub fn get_max_order_size(
+        &self,
+        market_index: usize,
+        side: Side,
+        price: NativePrice,
+        greeks: &Greeks,
+        zeta_group: &ZetaGroup,
+        spot: NativePrice,
+        margin_concession_percentage: Option<u8>,
+    ) -> Result<Lots> {
+        let state = self.simulate_order(
+            market_index,
+            side,
+            price,
+            Lots::ZERO,
+            greeks,
+            zeta_group,
+            spot,
+            margin_concession_percentage,
+        )?;
+        if state.available_balance_initial <= 0 {
+            return Ok(Lots::ZERO);
+        }
+        let mut available_balance = (state.available_balance_initial as u128)
+            .checked_mul(POSITION_PRECISION_DENOMINATOR)
+            .ok_or_else(math_error!(MathOverflow))?;
+
+        let ledger = self.product_ledgers[market_index];
+        let mark_price = greeks.mark_prices[market_index];
+        let product = &zeta_group.products[market_index];
+        let premium_through_mark: u128 = match side {
+            Side::Bid => price.get().saturating_sub(mark_price),
+            _ => mark_price.saturating_sub(price.get()),
+        }
+        .into();
+
+        // Lots that can be bought with `balance` at `cost` per lot.
+        let affordable_lots = |balance: u128, cost: u128| -> u128 {
+            if cost == 0 {
+                u64::MAX.into()
+            } else {
+                balance / cost
+            }
+        };
+
+        let mut free_lots: u128 = 0;
+        if !ledger.position.check_open(side) {
+            free_lots = ledger
+                .position
+                .size_abs()
+                .saturating_sub(ledger.order_state.closing_orders)
+                .into();
+        }
+        if product.kind == Kind::Future && !self.is_market_maker() {
+            let (side_index, other_index) = match side {
+                Side::Bid => (BID_ORDERS_INDEX, ASK_ORDERS_INDEX),
+                _ => (ASK_ORDERS_INDEX, BID_ORDERS_INDEX),
+            };
+            let mut lots = [
+                ledger.order_state.opening_orders[BID_ORDERS_INDEX],
+                ledger.order_state.opening_orders[ASK_ORDERS_INDEX],
+            ];
+            if ledger.position.size > 0 {
+                lots[BID_ORDERS_INDEX] += ledger.position.size_abs();
+            } else if ledger.position.size < 0 {
+                lots[ASK_ORDERS_INDEX] += ledger.position.size_abs();
+            }
+            free_lots = free_lots
+                .checked_add(lots[other_index].saturating_sub(lots[side_index]).into())
+                .ok_or_else(math_error!(MathOverflow))?;
+        }
+
+        let free_size = free_lots.min(affordable_lots(available_balance, premium_through_mark));
+        available_balance = available_balance
+            .checked_sub(
+                free_size
+                    .checked_mul(premium_through_mark)
+                    .ok_or_else(math_error!(MathOverflow))?,
+            )
+            .ok_or_else(math_error!(MathUnderflow))?;
+
+        let mut size = free_size;
+        if free_size == free_lots {
+            let mut margin_per_lot: u128 = get_initial_margin_per_lot(
+                spot,
+                NativePrice(product.strike.get_strike()?),
+                NativePrice(mark_price),
+                product.kind,
+                side,
+                &zeta_group.margin_parameters,
+            )?
+            .get()
+            .into();
+            if self.is_market_maker() {
+                margin_per_lot = margin_per_lot
+                    .checked_mul(
+                        self.validate_margin_concession(margin_concession_percentage)?
+                            .into(),
+                    )
+                    .ok_or_else(math_error!(MathOverflow))?
+                    .checked_div(100)
+                    .ok_or_else(math_error!(DivisionByZero))?;
+            }
+            let cost_per_lot = margin_per_lot
+                .checked_add(premium_through_mark)
+                .ok_or_else(math_error!(MathOverflow))?;
+            if cost_per_lot == 0 {
+                return wrap_error!(Err(error!(FuzeErrorCode::UnpricedMarket)));
+            }
+            size = size
+                .checked_add(affordable_lots(available_balance, cost_per_lot))
+                .ok_or_else(math_error!(MathOverflow))?;
+        }
+        // Keep the ledger's order and position sizes in range.
+        let size_limit = u64::MAX
+            .saturating_sub(ledger.position.size_abs())
+            .saturating_sub(ledger.order_state.closing_orders)
+            .saturating_sub(ledger.order_state.opening_orders[BID_ORDERS_INDEX])
+            .saturating_sub(ledger.order_state.opening_orders[ASK_ORDERS_INDEX]);
+        let size: u64 = size
+            .min(size_limit.into())
+            .try_into()
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))?;
+
+        // Per lot costs are rounded differently to the account totals, so
+        // bisect `[0, size]` for the largest size within the initial margin.
+        let fits = |size: u64| -> Result<bool> {
+            Ok(self
+                .simulate_order(
+                    market_index,
+                    side,
+                    price,
+                    Lots(size),
+                    greeks,
+                    zeta_group,
+                    spot,
+                    margin_concession_percentage,
+                )?
+                .available_balance_initial
+                >= 0)
+        };
+        if fits(size)? {
+            return Ok(Lots(size));
+        }
+        let (mut fitting, mut exceeding) = (0, size);
+        while exceeding - fitting > 1 {
+            let mid = fitting + (exceeding - fitting) / 2;
+            if fits(mid)? {
+                fitting = mid;
+            } else {
+                exceeding = mid;
+            }
+        }
 
-        maintenance_margin_requirement
+        Ok(Lots(fitting))
     }
 
     pub fn is_market_maker(&self) -> bool {
@@ -847,25 +1364,30 @@ impl MarginAccount {
         &self,
         greeks: &Greeks,
         zeta_group: &ZetaGroup,
-        spot: u64,
+        spot: NativePrice,
         concession: u8,
-    ) -> u64 {
-        let maintenance_margin_requirement = self
-            .product_ledgers
-            .iter()
-            .enumerate()
-            .map(|(i, product_ledger)| {
-                product_ledger.get_margin_market_maker_concession(
-                    greeks.mark_prices[i],
+    ) -> Result<NativePrice> {
+        self.product_ledgers.iter().enumerate().try_fold(
+            NativePrice::ZERO,
+            |total, (i, product_ledger)| {
+                total.checked_add(product_ledger.get_margin_market_maker_concession(
+                    NativePrice(greeks.mark_prices[i]),
                     &zeta_group.products[i],
                     spot,
                     &zeta_group.margin_parameters,
                     concession,
-                )
-            })
-            .sum();
+                )?)
+            },
+        )
+    }
 
-        maintenance_margin_requirement
+    /// The requirement Zeta checks new orders against for this account type.
+    pub fn get_initial_margin_requirement_type(&self) -> MarginRequirement {
+        if self.is_market_maker() {
+            MarginRequirement::MarketMakerConcession
+        } else {
+            MarginRequirement::Initial
+        }
     }
 
     pub fn get_margin_requirement(
@@ -873,9 +1395,9 @@ impl MarginAccount {
         margin_type: MarginRequirement,
         greeks: &Greeks,
         zeta_group: &ZetaGroup,
-        native_spot: u64,
+        native_spot: NativePrice,
         margin_concession_percentage: Option<u8>,
-    ) -> u64 {
+    ) -> Result<NativePrice> {
         match margin_type {
             MarginRequirement::Initial => {
                 self.get_initial_margin(&greeks, &zeta_group, native_spot)
@@ -887,10 +1409,8 @@ impl MarginAccount {
                 self.get_maintenance_margin_including_orders(&greeks, &zeta_group, native_spot)
             }
             MarginRequirement::MarketMakerConcession => {
-                assert!(self.is_market_maker());
-                assert!(margin_concession_percentage.is_some());
-                let margin_concession_percentage = margin_concession_percentage.unwrap();
-                assert!(margin_concession_percentage > 0 && margin_concession_percentage <= 100);
+                let margin_concession_percentage =
+                    self.validate_margin_concession(margin_concession_percentage)?;
                 self.get_margin_market_maker_concession(
                     &greeks,
                     &zeta_group,
@@ -900,6 +1420,22 @@ impl MarginAccount {
             }
         }
     }
+
+    /// Returns the concession percentage if it applies to this account.
+    pub fn validate_margin_concession(
+        &self,
+        margin_concession_percentage: Option<u8>,
+    ) -> Result<u8> {
+        if !self.is_market_maker() {
+            return wrap_error!(Err(error!(FuzeErrorCode::AccountNotMarketMaker)));
+        }
+        match margin_concession_percentage {
+            Some(percentage) if percentage > 0 && percentage <= 100 => Ok(percentage),
+            _ => wrap_error!(Err(error!(
+                FuzeErrorCode::InvalidMarginConcessionPercentage
+            ))),
+        }
+    }
 }
 
 // Enum Types
@@ -923,8 +1459,22 @@ pub enum Kind {
     Perp = 4,
 }
 
+/// Loss against mark of an order of `size` lots filling at `price`.
+fn get_crossing_loss(
+    side: Side,
+    price: NativePrice,
+    size: Lots,
+    mark_price: NativePrice,
+) -> Result<i64> {
+    let premium_through_mark = match side {
+        Side::Bid => price.saturating_sub(mark_price),
+        _ => mark_price.saturating_sub(price),
+    };
+    to_signed(premium_through_mark.checked_mul_lots(size)?.get())
+}
+
 #[repr(u8)]
-#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy)]
+#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Side {
     Uninitialized = 0,
     Bid = 1,
@@ -932,15 +1482,16 @@ pub enum Side {
 }
 
 #[repr(u8)]
-#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy)]
+#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum OrderType {
     Limit = 0,
     PostOnly = 1,
     FillOrKill = 2,
+    ImmediateOrCancel = 3, // Fills what it can, the rest is cancelled.
 }
 
 #[repr(u8)]
-#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy)]
+#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Asset {
     SOL = 0,
     BTC = 1,
@@ -949,14 +1500,14 @@ pub enum Asset {
 }
 
 #[repr(u8)]
-#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy)]
+#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum MarginAccountType {
     Normal = 0,
     MarketMaker = 1,
 }
 
 #[repr(u8)]
-#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy)]
+#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum MovementType {
     Undefined = 0,
     Lock = 1,   // Margin account to spread
@@ -969,3 +1520,185 @@ pub enum MarginRequirement {
     MaintenanceIncludingOrders = 2, // Maintenance margin for all positions and open orders; used on new inserts that only close existing positions
     MarketMakerConcession = 3,      // See usage.
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    // A future marked at 100 with 5% initial margin and an unpriced call.
+    fn zeta_group_and_greeks() -> (Box<ZetaGroup>, Box<Greeks>) {
+        let mut zeta_group = Box::new(ZetaGroup::zeroed());
+        for (i, kind) in [(0, Kind::Call), (NUM_STRIKES * 2, Kind::Future)] {
+            zeta_group.products[i].kind = kind;
+            zeta_group.products[i].strike = Strike {
+                is_set: true,
+                value: 100_000_000,
+            };
+        }
+        zeta_group.margin_parameters.future_margin_initial = 5_000_000;
+
+        let mut greeks = Box::new(Greeks::zeroed());
+        greeks.mark_prices[NUM_STRIKES * 2] = 100_000_000;
+        (zeta_group, greeks)
+    }
+
+    #[test]
+    fn test_get_max_order_size() {
+        let (zeta_group, greeks) = zeta_group_and_greeks();
+        let mut margin_account = Box::new(MarginAccount::zeroed());
+        margin_account.balance = 10_000_000;
+        let max_order_size = |margin_account: &MarginAccount, side, price| {
+            margin_account.get_max_order_size(
+                NUM_STRIKES * 2,
+                side,
+                NativePrice(price),
+                &greeks,
+                &zeta_group,
+                NativePrice(100_000_000),
+                None,
+            )
+        };
+
+        // 5 of margin per lot at the mark.
+        assert_eq!(
+            max_order_size(&margin_account, Side::Bid, 100_000_000).unwrap(),
+            Lots(2_000)
+        );
+        // Bidding 5 through the mark doubles the cost of a lot.
+        assert_eq!(
+            max_order_size(&margin_account, Side::Bid, 105_000_000).unwrap(),
+            Lots(1_000)
+        );
+
+        // Closing lots are free, the rest of the balance still opens 2 lots.
+        margin_account.product_ledgers[NUM_STRIKES * 2].position = Position {
+            size: 3_000,
+            cost_of_trades: 300_000_000,
+        };
+        margin_account.balance = 25_000_000;
+        assert_eq!(
+            max_order_size(&margin_account, Side::Ask, 100_000_000).unwrap(),
+            Lots(8_000)
+        );
+
+        margin_account.balance = 0;
+        margin_account.product_ledgers[NUM_STRIKES * 2].position = Position::default();
+        assert_eq!(
+            max_order_size(&margin_account, Side::Bid, 100_000_000).unwrap(),
+            Lots::ZERO
+        );
+    }
+
+    #[test]
+    fn test_get_max_order_size_unpriced_market() {
+        let (zeta_group, greeks) = zeta_group_and_greeks();
+        let mut margin_account = Box::new(MarginAccount::zeroed());
+        margin_account.balance = 10_000_000;
+        assert!(margin_account
+            .get_max_order_size(
+                0,
+                Side::Bid,
+                NativePrice(0),
+                &greeks,
+                &zeta_group,
+                NativePrice(100_000_000),
+                None,
+            )
+            .is_err());
+    }
+
+    // Calls and puts struck at 100 and 110 in the first two series.
+    fn spread_zeta_group() -> Box<ZetaGroup> {
+        let mut zeta_group = Box::new(ZetaGroup::zeroed());
+        for expiry_index in 0..2 {
+            let head = expiry_index * NUM_PRODUCTS_PER_SERIES;
+            for (i, strike) in [(0, 100_000_000), (1, 110_000_000)] {
+                for (offset, kind) in [(0, Kind::Call), (NUM_STRIKES, Kind::Put)] {
+                    let product = &mut zeta_group.products[head + offset + i];
+                    product.kind = kind;
+                    product.strike = Strike {
+                        is_set: true,
+                        value: strike,
+                    };
+                }
+            }
+        }
+        zeta_group
+    }
+
+    fn position(size: i64, cost_of_trades: u64) -> Position {
+        Position {
+            size,
+            cost_of_trades,
+        }
+    }
+
+    #[test]
+    fn test_get_series_margin() {
+        let zeta_group = spread_zeta_group();
+        let mut spread_account = Box::new(SpreadAccount::zeroed());
+        assert_eq!(
+            spread_account.get_series_margin(&zeta_group, 0).unwrap(),
+            NativePrice::ZERO
+        );
+
+        // Bull call spread for a debit of 3 loses the debit below 100.
+        spread_account.positions[0] = position(1_000, 5_000_000);
+        spread_account.positions[1] = position(-1_000, 2_000_000);
+        assert_eq!(
+            spread_account.get_series_margin(&zeta_group, 0).unwrap(),
+            NativePrice(3_000_000)
+        );
+
+        // Bull put spread for a credit of 4 loses the width less the credit
+        // below 100.
+        spread_account.positions = [Position::default(); 46];
+        spread_account.positions[NUM_STRIKES] = position(1_000, 3_000_000);
+        spread_account.positions[NUM_STRIKES + 1] = position(-1_000, 7_000_000);
+        assert_eq!(
+            spread_account.get_series_margin(&zeta_group, 0).unwrap(),
+            NativePrice(6_000_000)
+        );
+
+        // A short put alone loses the most at a spot of zero.
+        spread_account.positions[NUM_STRIKES] = Position::default();
+        assert_eq!(
+            spread_account.get_series_margin(&zeta_group, 0).unwrap(),
+            NativePrice(103_000_000)
+        );
+    }
+
+    #[test]
+    fn test_get_series_margin_undefined_risk() {
+        let zeta_group = spread_zeta_group();
+        let mut spread_account = Box::new(SpreadAccount::zeroed());
+        spread_account.positions[1] = position(-1_000, 2_000_000);
+        assert!(spread_account.get_series_margin(&zeta_group, 0).is_err());
+
+        // Covered by a long call, whatever its strike.
+        spread_account.positions[0] = position(1_000, 5_000_000);
+        assert!(spread_account.get_series_margin(&zeta_group, 0).is_ok());
+    }
+
+    #[test]
+    fn test_get_margin() {
+        let zeta_group = spread_zeta_group();
+        let mut spread_account = Box::new(SpreadAccount::zeroed());
+        spread_account.balance = 10_000_000;
+        spread_account.positions[0] = position(1_000, 5_000_000);
+        spread_account.positions[1] = position(-1_000, 2_000_000);
+        spread_account.positions[NUM_PRODUCTS_PER_SERIES] = position(2_000, 10_000_000);
+        spread_account.positions[NUM_PRODUCTS_PER_SERIES + 1] = position(-2_000, 4_000_000);
+
+        // Debits of 3 and 6 in the two series.
+        assert_eq!(
+            spread_account.get_margin(&zeta_group).unwrap(),
+            NativePrice(9_000_000)
+        );
+        assert_eq!(
+            spread_account.get_excess_balance(&zeta_group).unwrap(),
+            NativePrice(1_000_000)
+        );
+    }
+}
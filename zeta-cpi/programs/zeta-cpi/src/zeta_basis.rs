@@ -0,0 +1,200 @@
+use crate::*;
+
+// Rates are annualised and have `PRICING_PRECISION`, like `Greeks.interest_rate`.
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ParityForward {
+    pub strike: NativePrice,        // Strike of the call/put pair.
+    pub forward_price: NativePrice, // Forward implied by put-call parity.
+    pub implied_interest_rate: i64, // Annualised rate implied by the forward.
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct FuturesBasis {
+    pub expiry_ts: u64,                      // Expiry of the series.
+    pub spot_price: NativePrice,             // Oracle spot price.
+    pub futures_price: NativePrice,          // Futures mark price.
+    pub basis: i64,                          // Futures price less spot.
+    pub implied_interest_rate: i64,          // Annualised rate implied by the future.
+    pub interest_rate: i64,                  // Rate of the series in `Greeks`.
+    pub interest_rate_difference: i64,       // Implied rate less the greeks rate.
+    pub parity_forwards: Vec<ParityForward>, // One per strike that is set.
+}
+
+pub fn get_futures_basis(futures_price: NativePrice, spot_price: NativePrice) -> Result<i64> {
+    to_signed(futures_price.get())?
+        .checked_sub(to_signed(spot_price.get())?)
+        .ok_or_else(math_error!(MathUnderflow))
+}
+
+/// Annualised continuously compounded rate implied by a forward,
+/// `ln(F / S) / T`.
+pub fn get_implied_interest_rate(
+    forward_price: NativePrice,
+    spot_price: NativePrice,
+    time_to_expiry: Decimal,
+) -> Result<Decimal> {
+    if forward_price == NativePrice::ZERO || spot_price == NativePrice::ZERO {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidPrice)));
+    }
+    if time_to_expiry <= Decimal::ZERO {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidTimeToExpiry)));
+    }
+
+    forward_price
+        .to_decimal()
+        .checked_div(spot_price.to_decimal())
+        .ok_or_else(math_error!(DivisionByZero))?
+        .ln()
+        .checked_div(time_to_expiry)
+        .ok_or_else(math_error!(DivisionByZero))
+}
+
+/// Forward implied by put-call parity, `K + (C - P) * e^(rT)`.
+/// The call/put spread is compounded at `interest_rate`.
+pub fn get_parity_forward(
+    call_price: NativePrice,
+    put_price: NativePrice,
+    strike: NativePrice,
+    interest_rate: Decimal,
+    time_to_expiry: Decimal,
+) -> Result<NativePrice> {
+    let compounding = get_growth_factor(interest_rate, time_to_expiry)?;
+    let forward = call_price
+        .to_decimal()
+        .checked_sub(put_price.to_decimal())
+        .ok_or_else(math_error!(MathUnderflow))?
+        .checked_mul(compounding)
+        .ok_or_else(math_error!(MathOverflow))?
+        .checked_add(strike.to_decimal())
+        .ok_or_else(math_error!(MathOverflow))?;
+    decimal_to_native(forward).map(NativePrice)
+}
+
+/// Compares the future of an expiry series with the oracle spot and the
+/// forwards implied by the series' option marks.
+///
+/// A future or parity forward implying a rate far from `Greeks.interest_rate`
+/// is mispriced relative to the surface the options are marked on.
+pub fn calculate_futures_basis(
+    zeta_group: &ZetaGroup,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+    expiry_index: usize,
+    now_ts: u64,
+) -> Result<FuturesBasis> {
+    let expiry_ts = zeta_group.expiry_series[expiry_index].expiry_ts;
+    let time_to_expiry = get_time_to_expiry(expiry_ts, now_ts)?;
+    let interest_rate = get_interest_rate(greeks, expiry_index);
+    let futures_price = NativePrice(greeks.get_futures_price(expiry_index));
+    let implied_interest_rate =
+        get_implied_interest_rate(futures_price, spot_price, time_to_expiry)?;
+
+    // Calls and puts of the same strike are NUM_STRIKES apart in a series.
+    let products = zeta_group.get_products_slice(expiry_index);
+    let mark_prices = greeks.get_mark_prices_slice(expiry_index);
+    let mut parity_forwards = Vec::with_capacity(NUM_STRIKES);
+    for strike_index in 0..NUM_STRIKES {
+        let strike = match products[strike_index].strike.get_strike() {
+            Ok(strike) => NativePrice(strike),
+            Err(_) => continue,
+        };
+        let forward_price = get_parity_forward(
+            NativePrice(mark_prices[strike_index]),
+            NativePrice(mark_prices[strike_index + NUM_STRIKES]),
+            strike,
+            interest_rate,
+            time_to_expiry,
+        )?;
+        // Deep in the money pairs can imply a non positive forward.
+        if forward_price == NativePrice::ZERO {
+            continue;
+        }
+
+        parity_forwards.push(ParityForward {
+            strike,
+            forward_price,
+            implied_interest_rate: decimal_to_rate(get_implied_interest_rate(
+                forward_price,
+                spot_price,
+                time_to_expiry,
+            )?)?,
+        });
+    }
+
+    let implied_interest_rate = decimal_to_rate(implied_interest_rate)?;
+    let interest_rate = greeks.interest_rate[expiry_index];
+    Ok(FuturesBasis {
+        expiry_ts,
+        spot_price,
+        futures_price,
+        basis: get_futures_basis(futures_price, spot_price)?,
+        implied_interest_rate,
+        interest_rate,
+        interest_rate_difference: implied_interest_rate
+            .checked_sub(interest_rate)
+            .ok_or_else(math_error!(MathUnderflow))?,
+        parity_forwards,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_futures_basis() {
+        let basis = |futures_price, spot_price| {
+            get_futures_basis(NativePrice(futures_price), NativePrice(spot_price))
+        };
+        assert_eq!(basis(101_000_000, 100_000_000).unwrap(), 1_000_000);
+        assert_eq!(basis(99_000_000, 100_000_000).unwrap(), -1_000_000);
+        assert!(basis(u64::MAX, 0).is_err());
+    }
+
+    #[test]
+    fn test_get_parity_forward() {
+        let forward = |call_price, put_price, interest_rate| {
+            get_parity_forward(
+                NativePrice(call_price),
+                NativePrice(put_price),
+                NativePrice(100_000_000),
+                interest_rate,
+                Decimal::ONE,
+            )
+        };
+
+        // Without rates the forward is the strike plus the call/put spread.
+        assert_eq!(
+            forward(12_000_000, 7_000_000, Decimal::ZERO).unwrap(),
+            NativePrice(105_000_000)
+        );
+        // Deep in the money puts floor the forward at zero.
+        assert_eq!(
+            forward(0, 200_000_000, Decimal::ZERO).unwrap(),
+            NativePrice::ZERO
+        );
+        assert!(forward(12_000_000, 7_000_000, Decimal::from(1_000)).is_err());
+    }
+
+    #[test]
+    fn test_get_implied_interest_rate() {
+        // A 5% rate round trips through the parity forward of an at the money
+        // pair at spot 100, whose call/put spread is S - K * e^(-rT).
+        let rate = Decimal::new(5, 2);
+        let half_year = Decimal::new(5, 1);
+        let spot = NativePrice(100_000_000);
+        let spread = NativePrice::from_decimal(
+            spot.to_decimal() - spot.to_decimal() / get_growth_factor(rate, half_year).unwrap(),
+        )
+        .unwrap();
+        let forward = get_parity_forward(spread, NativePrice::ZERO, spot, rate, half_year).unwrap();
+        let implied = get_implied_interest_rate(forward, spot, half_year).unwrap();
+        // Within the rounding of native prices.
+        assert!((decimal_to_rate(implied).unwrap() - 50_000_000_000).abs() < 1_000_000);
+
+        assert!(get_implied_interest_rate(NativePrice::ZERO, spot, half_year).is_err());
+        assert!(get_implied_interest_rate(spot, NativePrice::ZERO, half_year).is_err());
+        assert!(get_implied_interest_rate(spot, spot, Decimal::ZERO).is_err());
+    }
+}
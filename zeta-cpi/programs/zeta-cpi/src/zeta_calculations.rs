@@ -1,47 +1,597 @@
 use crate::*;
+use anchor_lang::Discriminator;
+use std::convert::TryFrom;
 
-#[derive(Debug)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct MarginAccountState {
-    pub balance: u64,                       // Balance of the margin account.
-    pub initial_margin: u64,                // Initial margin requirements of orders and positions.
-    pub maintenance_margin: u64,            // Maintenance margin requirements of positions.
+    pub balance: NativePrice,               // Balance of the margin account.
+    pub initial_margin: NativePrice,        // Initial (or MM concession) margin of orders.
+    pub maintenance_margin: NativePrice,    // Maintenance margin requirements of positions.
     pub unrealized_pnl: i64,                // Unrealized pnl of positions.
     pub available_balance_initial: i64,     // Available balance remaining for trading.
     pub available_balance_maintenance: i64, // Available balance before being at risk of liquidation
     pub account_equity: i64,                // Effective value of account
 }
 
+/// Market maker accounts are charged the concession margin from `State`
+/// instead of the plain initial margin.
+///
+/// `price_mode` picks the spot price of `oracle`, the reference price of
+/// `reference` (pass `oracle` again for its own TWAP, or an `OracleEma`), or
+/// whichever of the two leaves the account closer to liquidation.
 pub fn calculate_margin_account_state(
+    state: &State,
     zeta_group: &ZetaGroup,
     margin_account: &MarginAccount,
     greeks: &Greeks,
-    oracle: &AccountInfo,
+    oracle: &dyn OracleSource,
+    reference: &dyn OracleSource,
+    price_mode: PriceMode,
+) -> Result<MarginAccountState> {
+    let get_state = |spot_price| {
+        get_margin_account_state(
+            zeta_group,
+            margin_account,
+            greeks,
+            spot_price,
+            Some(state.margin_concession_percentage),
+        )
+    };
+
+    get_price_mode_state(oracle, reference, price_mode, get_state)
+}
+
+/// Runs `get_state`, a margin account state at a spot price, at the price
+/// `price_mode` picks from `oracle` and `reference`.
+pub fn get_price_mode_state(
+    oracle: &dyn OracleSource,
+    reference: &dyn OracleSource,
+    price_mode: PriceMode,
+    get_state: impl Fn(NativePrice) -> Result<MarginAccountState>,
+) -> Result<MarginAccountState> {
+    match price_mode {
+        PriceMode::Spot => get_state(get_native_oracle_price(oracle)?),
+        PriceMode::Twap => get_state(get_native_reference_price(reference)?),
+        PriceMode::Conservative => Ok(get_conservative_state(
+            get_state(get_native_oracle_price(oracle)?)?,
+            get_state(get_native_reference_price(reference)?)?,
+        )),
+    }
+}
+
+/// The state closer to liquidation, then with less balance for trading.
+pub fn get_conservative_state(
+    spot_state: MarginAccountState,
+    reference_state: MarginAccountState,
 ) -> MarginAccountState {
-    let spot_price = get_native_oracle_price(oracle);
-    let initial_margin = margin_account.get_initial_margin(greeks, zeta_group, spot_price);
-    let maintenance_margin = margin_account.get_maintenance_margin(greeks, zeta_group, spot_price);
-    let unrealized_pnl = margin_account.get_unrealized_pnl(greeks);
-    let available_balance_initial = (margin_account.balance as i64)
-        .checked_add(unrealized_pnl)
-        .unwrap()
-        .checked_sub(initial_margin as i64)
-        .unwrap();
-    let available_balance_maintenance = (margin_account.balance as i64)
-        .checked_add(unrealized_pnl)
-        .unwrap()
-        .checked_sub(maintenance_margin as i64)
-        .unwrap();
-    let account_equity = (margin_account.balance as i64)
-        .checked_add(unrealized_pnl)
-        .unwrap();
+    let is_reference_worse = (
+        reference_state.available_balance_maintenance,
+        reference_state.available_balance_initial,
+    ) < (
+        spot_state.available_balance_maintenance,
+        spot_state.available_balance_initial,
+    );
+    if is_reference_worse {
+        reference_state
+    } else {
+        spot_state
+    }
+}
+
+/// Same as `calculate_margin_account_state` for a given spot price.
+/// `margin_concession_percentage` is only required for market maker accounts.
+pub fn get_margin_account_state(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+    margin_concession_percentage: Option<u8>,
+) -> Result<MarginAccountState> {
+    let initial_margin = margin_account.get_margin_requirement(
+        margin_account.get_initial_margin_requirement_type(),
+        greeks,
+        zeta_group,
+        spot_price,
+        margin_concession_percentage,
+    )?;
+    let maintenance_margin =
+        margin_account.get_maintenance_margin(greeks, zeta_group, spot_price)?;
+    let unrealized_pnl = margin_account.get_unrealized_pnl(greeks)?;
+    MarginAccountState::new(
+        NativePrice(margin_account.balance),
+        initial_margin,
+        maintenance_margin,
+        unrealized_pnl,
+    )
+}
+
+/// Whether Zeta can liquidate `margin_account`, i.e. its equity is below the
+/// maintenance margin of its positions. Orders are not included.
+pub fn is_liquidatable(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+) -> Result<bool> {
+    let maintenance_margin =
+        margin_account.get_maintenance_margin(greeks, zeta_group, spot_price)?;
+    Ok(get_account_equity(margin_account, greeks)? < to_signed(maintenance_margin.get())?)
+}
+
+/// Balance plus unrealized pnl of the positions at mark.
+pub(crate) fn get_account_equity(margin_account: &MarginAccount, greeks: &Greeks) -> Result<i64> {
+    to_signed(margin_account.balance)?
+        .checked_add(margin_account.get_unrealized_pnl(greeks)?)
+        .ok_or_else(math_error!(MathOverflow))
+}
+
+/// Errors unless `margin_account` is liquidatable. Checked before a
+/// liquidation is submitted so the transaction fails early and cheaply.
+pub fn validate_liquidatable(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+) -> Result<()> {
+    if !is_liquidatable(zeta_group, margin_account, greeks, spot_price)? {
+        return wrap_error!(Err(error!(FuzeErrorCode::AccountNotLiquidatable)));
+    }
+    Ok(())
+}
+
+/// Whether Zeta will force cancel the orders of `margin_account`: it has
+/// active orders and is either flagged or its equity no longer covers the
+/// maintenance margin of its positions and orders.
+pub fn is_force_cancellable(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+) -> Result<bool> {
+    if !margin_account.has_active_orders() {
+        return Ok(false);
+    }
+    if margin_account.force_cancel_flag {
+        return Ok(true);
+    }
+
+    let maintenance_margin =
+        margin_account.get_maintenance_margin_including_orders(greeks, zeta_group, spot_price)?;
+    Ok(get_account_equity(margin_account, greeks)? < to_signed(maintenance_margin.get())?)
+}
+
+/// Errors unless the orders of `margin_account` can be force cancelled.
+pub fn validate_force_cancellable(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+) -> Result<()> {
+    if !is_force_cancellable(zeta_group, margin_account, greeks, spot_price)? {
+        return wrap_error!(Err(error!(FuzeErrorCode::AccountNotForceCancellable)));
+    }
+    Ok(())
+}
+
+/// Returns the keys of the accounts in `margin_accounts` whose orders can be
+/// force cancelled. Accounts that aren't Zeta margin accounts are skipped, so
+/// a keeper can pass any batch it tracks.
+pub fn get_force_cancellable_accounts(
+    zeta_program: &Pubkey,
+    zeta_group: &ZetaGroup,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+    margin_accounts: &[AccountInfo],
+) -> Result<Vec<Pubkey>> {
+    let mut force_cancellable = Vec::new();
+    for account in margin_accounts {
+        if account.owner != zeta_program || !is_margin_account(account)? {
+            continue;
+        }
+
+        let margin_account = deserialize_account_info_zerocopy::<MarginAccount>(account)?;
+        if is_force_cancellable(zeta_group, &margin_account, greeks, spot_price)? {
+            force_cancellable.push(account.key());
+        }
+    }
+    Ok(force_cancellable)
+}
+
+fn is_margin_account(account: &AccountInfo) -> Result<bool> {
+    let data = account.try_borrow_data()?;
+    Ok(
+        data.len() == DISCRIMINATOR_SIZE + std::mem::size_of::<MarginAccount>()
+            && data[..DISCRIMINATOR_SIZE] == MarginAccount::discriminator(),
+    )
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SpreadAccountState {
+    pub balance: NativePrice,        // Balance of the spread account.
+    pub margin: NativePrice,         // Max loss at expiry of the spreads held.
+    pub unrealized_pnl: i64,         // Unrealized pnl of positions.
+    pub excess_balance: NativePrice, // Balance that can be transferred back to the margin account.
+    pub account_equity: i64,         // Effective value of account
+}
 
-    MarginAccountState {
-        balance: margin_account.balance,
+pub fn calculate_spread_account_state(
+    zeta_group: &ZetaGroup,
+    spread_account: &SpreadAccount,
+    greeks: &Greeks,
+) -> Result<SpreadAccountState> {
+    let margin = spread_account.get_margin(zeta_group)?;
+    let unrealized_pnl = spread_account.get_unrealized_pnl(greeks)?;
+    let balance = NativePrice(spread_account.balance);
+    Ok(SpreadAccountState {
+        balance,
+        margin,
+        unrealized_pnl,
+        excess_balance: balance.saturating_sub(margin),
+        account_equity: to_signed(spread_account.balance)?
+            .checked_add(unrealized_pnl)
+            .ok_or_else(math_error!(MathOverflow))?,
+    })
+}
+
+impl MarginAccountState {
+    pub fn new(
+        balance: NativePrice,
+        initial_margin: NativePrice,
+        maintenance_margin: NativePrice,
+        unrealized_pnl: i64,
+    ) -> Result<MarginAccountState> {
+        let account_equity = to_signed(balance.get())?
+            .checked_add(unrealized_pnl)
+            .ok_or_else(math_error!(MathOverflow))?;
+        let available_balance_initial = account_equity
+            .checked_sub(to_signed(initial_margin.get())?)
+            .ok_or_else(math_error!(MathUnderflow))?;
+        let available_balance_maintenance = account_equity
+            .checked_sub(to_signed(maintenance_margin.get())?)
+            .ok_or_else(math_error!(MathUnderflow))?;
+
+        Ok(MarginAccountState {
+            balance,
+            initial_margin,
+            maintenance_margin,
+            unrealized_pnl,
+            available_balance_initial,
+            available_balance_maintenance,
+            account_equity,
+        })
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum MarkPriceMode {
+    Reprice, // Move marks by their Black-Scholes change off the current vol surface.
+    Fixed,   // Hold marks at their current values, only margin moves with spot.
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LiquidationPrices {
+    pub lower: Option<NativePrice>, // Highest spot below the current price that is liquidatable.
+    pub upper: Option<NativePrice>, // Lowest spot above the current price that is liquidatable.
+}
+
+/// Returns the margin account state if spot moved from `current_spot_price`
+/// to `spot_price`.
+///
+/// # Arguments
+///
+/// * `mark_price_mode` - Whether marks are repriced at `spot_price` or held fixed,
+///   see `get_repriced_mark_prices`.
+/// * `now_ts` - Unix timestamp used for time to expiry when repricing.
+pub fn get_margin_account_state_at_spot(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    current_spot_price: NativePrice,
+    spot_price: NativePrice,
+    mark_price_mode: MarkPriceMode,
+    now_ts: u64,
+) -> Result<MarginAccountState> {
+    let mark_prices = match mark_price_mode {
+        MarkPriceMode::Reprice => {
+            get_repriced_mark_prices(zeta_group, greeks, current_spot_price, spot_price, now_ts)?
+        }
+        MarkPriceMode::Fixed => greeks.mark_prices,
+    };
+
+    let initial_margin =
+        margin_account.get_initial_margin_for_marks(&mark_prices, zeta_group, spot_price)?;
+    let maintenance_margin =
+        margin_account.get_maintenance_margin_for_marks(&mark_prices, zeta_group, spot_price)?;
+    let unrealized_pnl = margin_account.get_unrealized_pnl_for_marks(&mark_prices)?;
+    MarginAccountState::new(
+        NativePrice(margin_account.balance),
         initial_margin,
         maintenance_margin,
         unrealized_pnl,
-        available_balance_initial,
-        available_balance_maintenance,
-        account_equity,
+    )
+}
+
+/// Finds the spot prices below and above `spot_price` at which
+/// `available_balance_maintenance` drops below zero.
+///
+/// Spot is scanned in `LIQUIDATION_SEARCH_STEP_PERCENT` steps out to
+/// `LIQUIDATION_SEARCH_RANGE_MULTIPLE` in each direction, and the first
+/// crossing is then bisected to `LIQUIDATION_PRICE_TOLERANCE`.
+/// A side is `None` if no crossing is found within the range.
+/// If the account is already liquidatable, both sides are `spot_price`.
+pub fn calculate_liquidation_prices(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+    mark_price_mode: MarkPriceMode,
+    now_ts: u64,
+) -> Result<LiquidationPrices> {
+    let is_liquidatable = |spot: u64| -> Result<bool> {
+        let state = get_margin_account_state_at_spot(
+            zeta_group,
+            margin_account,
+            greeks,
+            spot_price,
+            NativePrice(spot),
+            mark_price_mode,
+            now_ts,
+        )?;
+        Ok(state.available_balance_maintenance < 0)
+    };
+
+    if is_liquidatable(spot_price.get())? {
+        return Ok(LiquidationPrices {
+            lower: Some(spot_price),
+            upper: Some(spot_price),
+        });
+    }
+
+    let spot_price = spot_price.get();
+    let lower_bound = spot_price
+        .checked_div(LIQUIDATION_SEARCH_RANGE_MULTIPLE)
+        .ok_or_else(math_error!(DivisionByZero))?
+        .max(1);
+    let upper_bound = spot_price
+        .checked_mul(LIQUIDATION_SEARCH_RANGE_MULTIPLE)
+        .ok_or_else(math_error!(MathOverflow))?;
+
+    let lower = find_liquidation_price(&is_liquidatable, spot_price, lower_bound)?;
+    let upper = find_liquidation_price(&is_liquidatable, spot_price, upper_bound)?;
+    Ok(LiquidationPrices {
+        lower: lower.map(NativePrice),
+        upper: upper.map(NativePrice),
+    })
+}
+
+/// Scans from a safe spot price towards `bound` and bisects the first crossing.
+fn find_liquidation_price(
+    is_liquidatable: &dyn Fn(u64) -> Result<bool>,
+    safe_price: u64,
+    bound: u64,
+) -> Result<Option<u64>> {
+    // Always moves at least one native unit so tiny prices still progress.
+    let step = |spot: u64| -> Result<u64> {
+        let spot = spot as u128;
+        let next = if bound < safe_price {
+            spot.checked_mul(100)
+                .ok_or_else(math_error!(MathOverflow))?
+                .checked_div(100 + LIQUIDATION_SEARCH_STEP_PERCENT)
+                .ok_or_else(math_error!(DivisionByZero))?
+                .min(spot - 1)
+                .max(bound.into())
+        } else {
+            spot.checked_mul(100 + LIQUIDATION_SEARCH_STEP_PERCENT)
+                .ok_or_else(math_error!(MathOverflow))?
+                .checked_div(100)
+                .ok_or_else(math_error!(DivisionByZero))?
+                .max(spot + 1)
+                .min(bound.into())
+        };
+        u64::try_from(next).map_err(|_| error!(FuzeErrorCode::MathOverflow))
+    };
+
+    let mut safe = safe_price;
+    let mut unsafe_price = None;
+    while safe != bound {
+        let next = step(safe)?;
+        if is_liquidatable(next)? {
+            unsafe_price = Some(next);
+            break;
+        }
+        safe = next;
+    }
+
+    let mut liquidatable = match unsafe_price {
+        Some(price) => price,
+        None => return Ok(None),
+    };
+
+    while safe.max(liquidatable) - safe.min(liquidatable) > LIQUIDATION_PRICE_TOLERANCE {
+        let mid = ((safe as u128 + liquidatable as u128) / 2) as u64;
+        if is_liquidatable(mid)? {
+            liquidatable = mid;
+        } else {
+            safe = mid;
+        }
+    }
+
+    Ok(Some(liquidatable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    const NOW_TS: u64 = 1_000_000;
+
+    // One lot long a future entered at 100 with 10 of balance, margined at 5%
+    // of spot. Zeta marks the future at 101 while it is worth 100 at spot 100.
+    fn future_account() -> (Box<ZetaGroup>, Box<Greeks>, Box<MarginAccount>) {
+        let mut zeta_group = Box::new(ZetaGroup::zeroed());
+        zeta_group.products[0].kind = Kind::Future;
+        zeta_group.products[0].strike = Strike {
+            is_set: true,
+            value: 0,
+        };
+        zeta_group.expiry_series[0].expiry_ts = NOW_TS + SECONDS_IN_A_YEAR;
+        zeta_group.margin_parameters.future_margin_maintenance = 5_000_000;
+
+        let mut greeks = Box::new(Greeks::zeroed());
+        greeks.mark_prices[0] = 101_000_000;
+
+        let mut margin_account = Box::new(MarginAccount::zeroed());
+        margin_account.balance = 10_000_000;
+        margin_account.product_ledgers[0].position = Position {
+            size: 1_000,
+            cost_of_trades: 100_000_000,
+        };
+        (zeta_group, greeks, margin_account)
+    }
+
+    #[test]
+    fn test_reprice_is_anchored_to_marks() {
+        let (zeta_group, greeks, margin_account) = future_account();
+        let state = |spot_price, mark_price_mode| {
+            get_margin_account_state_at_spot(
+                &zeta_group,
+                &margin_account,
+                &greeks,
+                NativePrice(100_000_000),
+                NativePrice(spot_price),
+                mark_price_mode,
+                NOW_TS,
+            )
+            .unwrap()
+            .account_equity
+        };
+
+        assert_eq!(
+            state(100_000_000, MarkPriceMode::Reprice),
+            state(100_000_000, MarkPriceMode::Fixed)
+        );
+        assert_eq!(state(100_000_000, MarkPriceMode::Reprice), 11_000_000);
+        assert_eq!(state(90_000_000, MarkPriceMode::Reprice), 1_000_000);
+        assert_eq!(state(90_000_000, MarkPriceMode::Fixed), 11_000_000);
+    }
+
+    #[test]
+    fn test_calculate_liquidation_prices() {
+        let (zeta_group, greeks, margin_account) = future_account();
+        let prices = |mark_price_mode| {
+            calculate_liquidation_prices(
+                &zeta_group,
+                &margin_account,
+                &greeks,
+                NativePrice(100_000_000),
+                mark_price_mode,
+                NOW_TS,
+            )
+            .unwrap()
+        };
+
+        // Fixed marks: 11 of equity against 5% of spot, liquidatable above 220.
+        let fixed = prices(MarkPriceMode::Fixed);
+        assert_eq!(fixed.lower, None);
+        let upper = fixed.upper.unwrap().get();
+        assert!(upper > 220_000_000 && upper - 220_000_000 <= LIQUIDATION_PRICE_TOLERANCE);
+
+        // Repriced: 11 + (spot - 100) against 5% of spot, liquidatable below 89 / 0.95.
+        let repriced = prices(MarkPriceMode::Reprice);
+        assert_eq!(repriced.upper, None);
+        let lower = repriced.lower.unwrap().get();
+        assert!(lower < 93_684_211 && 93_684_211 - lower <= LIQUIDATION_PRICE_TOLERANCE);
+
+        // Below maintenance already, both sides are the current spot.
+        let mut margin_account = margin_account;
+        margin_account.balance = 0;
+        let liquidatable = calculate_liquidation_prices(
+            &zeta_group,
+            &margin_account,
+            &greeks,
+            NativePrice(90_000_000),
+            MarkPriceMode::Fixed,
+            NOW_TS,
+        )
+        .unwrap();
+        assert_eq!(liquidatable.lower, Some(NativePrice(90_000_000)));
+        assert_eq!(liquidatable.upper, Some(NativePrice(90_000_000)));
+    }
+
+    fn margin_account_state(
+        available_balance_initial: i64,
+        available_balance_maintenance: i64,
+    ) -> MarginAccountState {
+        MarginAccountState {
+            balance: NativePrice::ZERO,
+            initial_margin: NativePrice::ZERO,
+            maintenance_margin: NativePrice::ZERO,
+            unrealized_pnl: 0,
+            available_balance_initial,
+            available_balance_maintenance,
+            account_equity: 0,
+        }
+    }
+
+    #[test]
+    fn test_get_conservative_state() {
+        let state = |spot_state, reference_state| {
+            let state = get_conservative_state(spot_state, reference_state);
+            (
+                state.available_balance_initial,
+                state.available_balance_maintenance,
+            )
+        };
+        assert_eq!(
+            state(margin_account_state(10, 20), margin_account_state(5, 30)),
+            (10, 20)
+        );
+        assert_eq!(
+            state(margin_account_state(10, 20), margin_account_state(15, 15)),
+            (15, 15)
+        );
+        // Ties on maintenance fall back to the initial balance.
+        assert_eq!(
+            state(margin_account_state(10, 20), margin_account_state(5, 20)),
+            (5, 20)
+        );
+        assert_eq!(
+            state(margin_account_state(10, 20), margin_account_state(10, 20)),
+            (10, 20)
+        );
+    }
+
+    #[test]
+    fn test_calculate_spread_account_state() {
+        // Bull call spread of 100 / 110 bought for 3, marked at 6 and 1.
+        let mut zeta_group = Box::new(ZetaGroup::zeroed());
+        for (i, strike) in [(0, 100_000_000), (1, 110_000_000)] {
+            zeta_group.products[i].kind = Kind::Call;
+            zeta_group.products[i].strike = Strike {
+                is_set: true,
+                value: strike,
+            };
+        }
+        let mut greeks = Box::new(Greeks::zeroed());
+        greeks.mark_prices[0] = 6_000_000;
+        greeks.mark_prices[1] = 1_000_000;
+        let mut spread_account = Box::new(SpreadAccount::zeroed());
+        spread_account.balance = 10_000_000;
+        spread_account.positions[0] = Position {
+            size: 1_000,
+            cost_of_trades: 5_000_000,
+        };
+        spread_account.positions[1] = Position {
+            size: -1_000,
+            cost_of_trades: 2_000_000,
+        };
+
+        let state = calculate_spread_account_state(&zeta_group, &spread_account, &greeks).unwrap();
+        assert_eq!(state.balance, NativePrice(10_000_000));
+        assert_eq!(state.margin, NativePrice(3_000_000));
+        assert_eq!(state.unrealized_pnl, 2_000_000);
+        assert_eq!(state.excess_balance, NativePrice(7_000_000));
+        assert_eq!(state.account_equity, 12_000_000);
     }
 }
@@ -28,14 +28,35 @@ pub trait ZetaInterface<'info, T: Accounts<'info>> {
         client_order_id: Option<u64>,
         tag: Option<String>,
     ) -> Result<()>;
+    fn place_order_v4(
+        ctx: Context<T>,
+        price: u64,
+        size: u64,
+        side: Side,
+        order_type: OrderType,
+        client_order_id: Option<u64>,
+        tag: Option<String>,
+        tif_offset: Option<u16>,
+    ) -> Result<()>;
     fn cancel_order(ctx: Context<T>, side: Side, order_id: u128) -> Result<()>;
+    fn cancel_order_by_client_order_id(ctx: Context<T>, client_order_id: u64) -> Result<()>;
     fn cancel_all_market_orders(ctx: Context<T>) -> Result<()>;
+    fn force_cancel_orders(ctx: Context<T>) -> Result<()>;
     fn position_movement(
         ctx: Context<T>,
         movement_type: MovementType,
         movements: Vec<PositionMovementArg>,
     ) -> Result<()>;
     fn transfer_excess_spread_balance(ctx: Context<T>) -> Result<()>;
+    fn liquidate(ctx: Context<T>, size: u64) -> Result<()>;
+    fn crank_event_queue(ctx: Context<T>) -> Result<()>;
+    fn settle_dex_funds(ctx: Context<T>) -> Result<()>;
+    fn close_margin_account(ctx: Context<T>) -> Result<()>;
+    fn close_spread_account(ctx: Context<T>) -> Result<()>;
+    fn close_open_orders(ctx: Context<T>, map_nonce: u8) -> Result<()>;
+    fn initialize_insurance_deposit_account(ctx: Context<T>, nonce: u8) -> Result<()>;
+    fn deposit_insurance_vault(ctx: Context<T>, amount: u64) -> Result<()>;
+    fn withdraw_insurance_vault(ctx: Context<T>, percentage_amount: u64) -> Result<()>;
 }
 
 pub fn initialize_margin_account<'info>(
@@ -134,6 +155,37 @@ pub fn place_order_v3<'info>(
     zeta_interface::place_order_v3(cpi_ctx, price, size, side, order_type, client_order_id, tag)
 }
 
+/// Same as `place_order_v3`, an order given a `tif_offset` expires that many
+/// seconds after it is placed. Zeta takes the time in force as its own
+/// argument, `OrderType` keeps the single byte layout it decodes.
+pub fn place_order_v4<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: PlaceOrder<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    price: u64,
+    size: u64,
+    side: Side,
+    order_type: OrderType,
+    client_order_id: Option<u64>,
+    tag: Option<String>, // Not stored, only used when sniffing the transactions
+    tif_offset: Option<u16>,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::place_order_v4(
+        cpi_ctx,
+        price,
+        size,
+        side,
+        order_type,
+        client_order_id,
+        tag,
+        tif_offset,
+    )
+}
+
 pub fn cancel_order<'info>(
     zeta_program: AccountInfo<'info>,
     cpi_accounts: CancelOrder<'info>,
@@ -148,6 +200,19 @@ pub fn cancel_order<'info>(
     zeta_interface::cancel_order(cpi_ctx, side, order_id)
 }
 
+pub fn cancel_order_by_client_order_id<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: CancelOrder<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    client_order_id: u64,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::cancel_order_by_client_order_id(cpi_ctx, client_order_id)
+}
+
 pub fn cancel_all_market_orders<'info>(
     zeta_program: AccountInfo<'info>,
     cpi_accounts: CancelOrder<'info>,
@@ -160,6 +225,20 @@ pub fn cancel_all_market_orders<'info>(
     zeta_interface::cancel_all_market_orders(cpi_ctx)
 }
 
+/// Permissionless, Zeta only accepts accounts flagged for force cancellation
+/// or short of maintenance margin including orders.
+pub fn force_cancel_orders<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: ForceCancelOrders<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::force_cancel_orders(cpi_ctx)
+}
+
 pub fn position_movement<'info>(
     zeta_program: AccountInfo<'info>,
     cpi_accounts: PositionMovement<'info>,
@@ -185,3 +264,127 @@ pub fn transfer_excess_spread_balance<'info>(
     }
     zeta_interface::transfer_excess_spread_balance(cpi_ctx)
 }
+
+/// Zeta liquidates the product of `cpi_accounts.market`.
+pub fn liquidate<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: Liquidate<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    size: u64,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::liquidate(cpi_ctx, size)
+}
+
+/// `open_orders` are passed as remaining accounts, see `CrankEventQueue`.
+pub fn crank_event_queue<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: CrankEventQueue<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    open_orders: &[AccountInfo<'info>],
+) -> Result<()> {
+    let mut cpi_ctx =
+        CpiContext::new(zeta_program, cpi_accounts).with_remaining_accounts(open_orders.to_vec());
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::crank_event_queue(cpi_ctx)
+}
+
+/// `open_orders` are passed as remaining accounts, see `SettleDexFunds`.
+pub fn settle_dex_funds<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: SettleDexFunds<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    open_orders: &[AccountInfo<'info>],
+) -> Result<()> {
+    let mut cpi_ctx =
+        CpiContext::new(zeta_program, cpi_accounts).with_remaining_accounts(open_orders.to_vec());
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::settle_dex_funds(cpi_ctx)
+}
+
+pub fn close_margin_account<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: CloseMarginAccount<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::close_margin_account(cpi_ctx)
+}
+
+pub fn close_spread_account<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: CloseSpreadAccount<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::close_spread_account(cpi_ctx)
+}
+
+/// `map_nonce` is the bump of `cpi_accounts.open_orders_map`.
+pub fn close_open_orders<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: CloseOpenOrders<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    map_nonce: u8,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::close_open_orders(cpi_ctx, map_nonce)
+}
+
+/// `nonce` is the bump of `cpi_accounts.insurance_deposit_account`.
+pub fn initialize_insurance_deposit_account<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: InitializeInsuranceDepositAccount<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    nonce: u8,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::initialize_insurance_deposit_account(cpi_ctx, nonce)
+}
+
+/// Deposits `amount` USDC (6.dp) into the insurance vault.
+pub fn deposit_insurance_vault<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: DepositInsuranceVault<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    amount: u64,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::deposit_insurance_vault(cpi_ctx, amount)
+}
+
+/// Withdraws `percentage_amount` percent of the insurance deposit.
+pub fn withdraw_insurance_vault<'info>(
+    zeta_program: AccountInfo<'info>,
+    cpi_accounts: WithdrawInsuranceVault<'info>,
+    signer_seeds: Option<&[&[&[u8]]]>,
+    percentage_amount: u64,
+) -> Result<()> {
+    let mut cpi_ctx = CpiContext::new(zeta_program, cpi_accounts);
+    if let Some(seeds) = signer_seeds {
+        cpi_ctx = cpi_ctx.with_signer(seeds);
+    }
+    zeta_interface::withdraw_insurance_vault(cpi_ctx, percentage_amount)
+}
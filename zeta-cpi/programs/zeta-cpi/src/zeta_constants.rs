@@ -12,6 +12,7 @@ pub const USER_INSURANCE_DEPOSIT_SEED: &str = "user-insurance-deposit";
 pub const WHITELIST_TRADING_FEES_SEED: &str = "whitelist-trading-fees";
 pub const SETTLEMENT_SEED: &str = "settlement";
 pub const MARGIN_SEED: &str = "margin";
+pub const SPREAD_SEED: &str = "spread";
 pub const UNDERLYING_SEED: &str = "underlying";
 pub const SERUM_SEED: &str = "serum";
 pub const MINT_AUTH_SEED: &str = "mint-auth";
@@ -63,6 +64,11 @@ pub const SECONDS_IN_A_YEAR: u64 = 31_536_000;
 pub const NATIVE_PRECISION_DENOMINATOR: u128 = 100_000_000; // 100%
 pub const POSITION_PRECISION_DENOMINATOR: u128 = 1_000;
 
+// Liquidation price search
+pub const LIQUIDATION_SEARCH_STEP_PERCENT: u128 = 2; // Scan spot in 2% steps.
+pub const LIQUIDATION_SEARCH_RANGE_MULTIPLE: u64 = 10; // Scan from spot / 10 to spot * 10.
+pub const LIQUIDATION_PRICE_TOLERANCE: u64 = 10_000; // $0.01
+
 // Futures
 pub const FUTURE_MARGIN_INITIAL: u128 = 15_000_000; // 15.0%
 pub const FUTURE_MARGIN_MAINTENANCE: u128 = 7_500_000; // 7.5%
@@ -209,3 +209,131 @@ pub struct TransferExcessSpreadBalance<'info> {
     pub spread_account: AccountInfo<'info>,
     pub authority: Signer<'info>,
 }
+
+// Remaining accounts: the open orders accounts referenced by the queued events.
+#[derive(Accounts, Clone)]
+pub struct CrankEventQueue<'info> {
+    pub state: AccountInfo<'info>,
+    pub zeta_group: AccountInfo<'info>,
+    #[account(mut)]
+    pub market: AccountInfo<'info>,
+    #[account(mut)]
+    pub event_queue: AccountInfo<'info>,
+    pub dex_program: AccountInfo<'info>,
+    pub serum_authority: AccountInfo<'info>,
+}
+
+// Remaining accounts: the open orders accounts to settle.
+#[derive(Accounts, Clone)]
+pub struct SettleDexFunds<'info> {
+    pub state: AccountInfo<'info>,
+    #[account(mut)]
+    pub market: AccountInfo<'info>,
+    #[account(mut)]
+    pub zeta_base_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub zeta_quote_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub dex_base_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub dex_quote_vault: AccountInfo<'info>,
+    pub vault_owner: AccountInfo<'info>,
+    pub mint_authority: AccountInfo<'info>,
+    pub serum_authority: AccountInfo<'info>,
+    pub dex_program: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts, Clone)]
+pub struct CloseMarginAccount<'info> {
+    #[account(mut)]
+    pub margin_account: AccountInfo<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub zeta_group: AccountInfo<'info>,
+}
+
+#[derive(Accounts, Clone)]
+pub struct CloseSpreadAccount<'info> {
+    #[account(mut)]
+    pub spread_account: AccountInfo<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub zeta_group: AccountInfo<'info>,
+}
+
+#[derive(Accounts, Clone)]
+pub struct CloseOpenOrders<'info> {
+    pub state: AccountInfo<'info>,
+    pub zeta_group: AccountInfo<'info>,
+    pub dex_program: AccountInfo<'info>,
+    #[account(mut)]
+    pub open_orders: AccountInfo<'info>,
+    #[account(mut)]
+    pub margin_account: AccountInfo<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub market: AccountInfo<'info>,
+    pub serum_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub open_orders_map: AccountInfo<'info>,
+}
+
+#[derive(Accounts, Clone)]
+pub struct InitializeInsuranceDepositAccount<'info> {
+    pub zeta_group: AccountInfo<'info>,
+    #[account(mut)]
+    pub insurance_deposit_account: AccountInfo<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub whitelist_insurance_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts, Clone)]
+pub struct DepositInsuranceVault<'info> {
+    pub state: AccountInfo<'info>,
+    #[account(mut)]
+    pub zeta_group: AccountInfo<'info>,
+    #[account(mut)]
+    pub insurance_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub insurance_deposit_account: AccountInfo<'info>,
+    #[account(mut)]
+    pub usdc_account: AccountInfo<'info>,
+    #[account(mut)]
+    pub zeta_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub socialized_loss_account: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts, Clone)]
+pub struct WithdrawInsuranceVault<'info> {
+    #[account(mut)]
+    pub zeta_group: AccountInfo<'info>,
+    #[account(mut)]
+    pub insurance_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub insurance_deposit_account: AccountInfo<'info>,
+    #[account(mut)]
+    pub user_token_account: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts, Clone)]
+pub struct ForceCancelOrders<'info> {
+    pub greeks: AccountInfo<'info>,
+    pub oracle: AccountInfo<'info>,
+    pub cancel_accounts: CancelAccounts<'info>,
+}
+
+/// Client accounts of the contexts nested two levels deep, which the
+/// generated `accounts` module doesn't re-export.
+pub mod client_accounts {
+    pub use super::__client_accounts_cancel_accounts::CancelAccounts;
+    pub use super::__client_accounts_market_accounts::MarketAccounts;
+}
@@ -0,0 +1,238 @@
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+impl From<OrderType> for Liquidity {
+    /// Only post only orders are guaranteed to rest on the book. Limit
+    /// orders are assumed to cross, which keeps estimates conservative.
+    fn from(order_type: OrderType) -> Liquidity {
+        match order_type {
+            OrderType::PostOnly => Liquidity::Maker,
+            OrderType::Limit | OrderType::FillOrKill | OrderType::ImmediateOrCancel => {
+                Liquidity::Taker
+            }
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default)]
+pub struct FeeRates {
+    // Futures, charged on the traded notional.
+    pub taker_trade_fee_percentage: Pct,
+    // Options, charged on the spot notional.
+    pub taker_underlying_fee_percentage: Pct,
+    // Zeta does not charge makers.
+    pub maker_fee_percentage: Pct,
+}
+
+impl FeeRates {
+    /// Whitelisted accounts (with a whitelist trading fees account) pay the
+    /// whitelist underlying fee on options.
+    pub fn from_state(state: &State, is_whitelisted: bool) -> FeeRates {
+        FeeRates {
+            taker_trade_fee_percentage: Pct(state.native_trade_fee_percentage),
+            taker_underlying_fee_percentage: Pct(if is_whitelisted {
+                state.native_whitelist_underlying_fee_percentage
+            } else {
+                state.native_underlying_fee_percentage
+            }),
+            maker_fee_percentage: Pct::ZERO,
+        }
+    }
+}
+
+/// Returns the fee of trading `size` lots.
+///
+/// Taker futures pay the trade fee on the traded notional, taker options pay
+/// the underlying fee on the spot notional.
+///
+/// # Arguments
+///
+/// * `rates` - Fee rates, see `FeeRates::from_state`.
+/// * `kind` - Product kind.
+/// * `price` - Order price.
+/// * `spot` - Underlying price.
+/// * `size` - Order size.
+/// * `liquidity` - Whether the order makes or takes liquidity.
+pub fn get_trade_fee(
+    rates: &FeeRates,
+    kind: Kind,
+    price: NativePrice,
+    spot: NativePrice,
+    size: Lots,
+    liquidity: Liquidity,
+) -> Result<NativePrice> {
+    let (notional_price, fee_percentage) = match (liquidity, kind) {
+        (_, Kind::Uninitialized) => {
+            return wrap_error!(Err(error!(FuzeErrorCode::UnsupportedKind)))
+        }
+        (Liquidity::Maker, _) => (price, rates.maker_fee_percentage),
+        (Liquidity::Taker, Kind::Future) | (Liquidity::Taker, Kind::Perp) => {
+            (price, rates.taker_trade_fee_percentage)
+        }
+        (Liquidity::Taker, Kind::Call) | (Liquidity::Taker, Kind::Put) => {
+            (spot, rates.taker_underlying_fee_percentage)
+        }
+    };
+
+    notional_price
+        .checked_mul_lots(size)?
+        .checked_mul_pct(fee_percentage)
+}
+
+/// Estimates the fee of an order with the rates in the Zeta `State`.
+pub fn estimate_order_fee(
+    state: &State,
+    kind: Kind,
+    price: NativePrice,
+    spot: NativePrice,
+    size: Lots,
+    order_type: OrderType,
+    is_whitelisted: bool,
+) -> Result<NativePrice> {
+    get_trade_fee(
+        &FeeRates::from_state(state, is_whitelisted),
+        kind,
+        price,
+        spot,
+        size,
+        order_type.into(),
+    )
+}
+
+/// Premium of an order net of its fee (6.dp). Positive when premium is
+/// received (asks) and negative when it is paid (bids).
+pub fn get_net_premium(
+    price: NativePrice,
+    size: Lots,
+    side: Side,
+    fee: NativePrice,
+) -> Result<i64> {
+    let premium = to_signed(price.checked_mul_lots(size)?.get())?;
+    let fee = to_signed(fee.get())?;
+
+    match side {
+        Side::Bid => premium
+            .checked_add(fee)
+            .map(|paid| -paid)
+            .ok_or_else(math_error!(MathOverflow)),
+        Side::Ask => premium
+            .checked_sub(fee)
+            .ok_or_else(math_error!(MathUnderflow)),
+        Side::Uninitialized => wrap_error!(Err(error!(FuzeErrorCode::InvalidSide))),
+    }
+}
+
+/// Estimates the premium of an order net of its fee.
+pub fn estimate_net_premium(
+    state: &State,
+    kind: Kind,
+    price: NativePrice,
+    spot: NativePrice,
+    size: Lots,
+    side: Side,
+    order_type: OrderType,
+    is_whitelisted: bool,
+) -> Result<i64> {
+    let fee = estimate_order_fee(state, kind, price, spot, size, order_type, is_whitelisted)?;
+    get_net_premium(price, size, side, fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0.05% trade fee, 0.03% underlying fee and 0.01% for whitelisted accounts.
+    fn state() -> State {
+        State {
+            native_trade_fee_percentage: 50_000,
+            native_underlying_fee_percentage: 30_000,
+            native_whitelist_underlying_fee_percentage: 10_000,
+            ..State::default()
+        }
+    }
+
+    #[test]
+    fn test_get_trade_fee() {
+        let rates = FeeRates::from_state(&state(), false);
+        let fee = |kind, liquidity| {
+            get_trade_fee(
+                &rates,
+                kind,
+                NativePrice(2_000_000),
+                NativePrice(100_000_000),
+                Lots(10_000),
+                liquidity,
+            )
+        };
+
+        // Futures on the traded notional of 20, options on the spot notional
+        // of 1000.
+        assert_eq!(
+            fee(Kind::Future, Liquidity::Taker).unwrap(),
+            NativePrice(10_000)
+        );
+        assert_eq!(
+            fee(Kind::Perp, Liquidity::Taker).unwrap(),
+            NativePrice(10_000)
+        );
+        assert_eq!(
+            fee(Kind::Call, Liquidity::Taker).unwrap(),
+            NativePrice(300_000)
+        );
+        assert_eq!(
+            fee(Kind::Put, Liquidity::Taker).unwrap(),
+            NativePrice(300_000)
+        );
+        assert_eq!(
+            fee(Kind::Call, Liquidity::Maker).unwrap(),
+            NativePrice::ZERO
+        );
+        assert!(fee(Kind::Uninitialized, Liquidity::Taker).is_err());
+        assert!(fee(Kind::Uninitialized, Liquidity::Maker).is_err());
+    }
+
+    #[test]
+    fn test_estimate_order_fee_whitelisted() {
+        let fee = |order_type, is_whitelisted| {
+            estimate_order_fee(
+                &state(),
+                Kind::Call,
+                NativePrice(2_000_000),
+                NativePrice(100_000_000),
+                Lots(10_000),
+                order_type,
+                is_whitelisted,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(fee(OrderType::Limit, true), NativePrice(100_000));
+        assert_eq!(
+            fee(OrderType::ImmediateOrCancel, false),
+            NativePrice(300_000)
+        );
+        assert_eq!(fee(OrderType::PostOnly, false), NativePrice::ZERO);
+    }
+
+    #[test]
+    fn test_get_net_premium() {
+        let premium = |side| {
+            get_net_premium(
+                NativePrice(2_000_000),
+                Lots(10_000),
+                side,
+                NativePrice(300_000),
+            )
+        };
+
+        // Bids pay the premium of 20 plus the fee, asks receive it less the fee.
+        assert_eq!(premium(Side::Bid).unwrap(), -20_300_000);
+        assert_eq!(premium(Side::Ask).unwrap(), 19_700_000);
+        assert!(premium(Side::Uninitialized).is_err());
+    }
+}
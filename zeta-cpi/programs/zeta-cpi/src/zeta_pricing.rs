@@ -0,0 +1,348 @@
+use crate::*;
+use std::convert::TryInto;
+
+/// Converts a native amount (6.dp) to a decimal.
+pub fn native_to_decimal(amount: u64) -> Decimal {
+    Decimal::from_i128_with_scale(amount as i128, PLATFORM_PRECISION)
+}
+
+/// Converts a decimal to a native amount (6.dp), rounding to the nearest unit.
+/// Negative amounts are floored at zero.
+pub fn decimal_to_native(amount: Decimal) -> Result<u64> {
+    amount
+        .max(Decimal::ZERO)
+        .checked_mul(Decimal::from(10u64.pow(PLATFORM_PRECISION)))
+        .ok_or_else(math_error!(MathOverflow))?
+        .round()
+        .to_u64()
+        .ok_or_else(math_error!(MathOverflow))
+}
+
+/// Returns the time to expiry in years, floored at zero.
+pub fn get_time_to_expiry(expiry_ts: u64, now_ts: u64) -> Result<Decimal> {
+    Decimal::from(expiry_ts.saturating_sub(now_ts))
+        .checked_div(Decimal::from(SECONDS_IN_A_YEAR))
+        .ok_or_else(math_error!(DivisionByZero))
+}
+
+/// Returns the interest rate of an expiry series as a decimal.
+/// Greeks stores the rate with `PRICING_PRECISION`.
+pub fn get_interest_rate(greeks: &Greeks, expiry_index: usize) -> Decimal {
+    Decimal::from_i128_with_scale(
+        greeks.interest_rate[expiry_index] as i128,
+        PRICING_PRECISION,
+    )
+}
+
+/// Converts a decimal rate to `PRICING_PRECISION`, the inverse of
+/// `get_interest_rate`.
+pub fn decimal_to_rate(rate: Decimal) -> Result<i64> {
+    rate.checked_mul(Decimal::from(10u64.pow(PRICING_PRECISION)))
+        .ok_or_else(math_error!(MathOverflow))?
+        .round()
+        .to_i64()
+        .ok_or_else(math_error!(MathOverflow))
+}
+
+/// Returns `e^(rT)`, the growth of a unit at `interest_rate` over
+/// `time_to_expiry` years.
+pub(crate) fn get_growth_factor(interest_rate: Decimal, time_to_expiry: Decimal) -> Result<Decimal> {
+    interest_rate
+        .checked_mul(time_to_expiry)
+        .ok_or_else(math_error!(MathOverflow))?
+        .checked_exp()
+        .ok_or_else(math_error!(MathOverflow))
+}
+
+/// Theoretical price of a future, `spot * e^(rT)`.
+pub fn get_future_price(
+    spot: Decimal,
+    interest_rate: Decimal,
+    time_to_expiry: Decimal,
+) -> Result<Decimal> {
+    spot.checked_mul(get_growth_factor(interest_rate, time_to_expiry)?)
+        .ok_or_else(math_error!(MathOverflow))
+}
+
+/// Black-Scholes price of a european option.
+///
+/// # Arguments
+///
+/// * `spot` - Underlying price.
+/// * `strike` - Option strike.
+/// * `volatility` - Annualized volatility, e.g. 0.8 for 80 points.
+/// * `interest_rate` - Annualized interest rate.
+/// * `time_to_expiry` - Time to expiry in years.
+/// * `kind` - Call or Put.
+pub fn get_black_scholes_price(
+    spot: Decimal,
+    strike: Decimal,
+    volatility: Decimal,
+    interest_rate: Decimal,
+    time_to_expiry: Decimal,
+    kind: Kind,
+) -> Result<Decimal> {
+    if kind != Kind::Call && kind != Kind::Put {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidOptionKind)));
+    }
+
+    // Intrinsic value at expiry or for degenerate inputs.
+    if time_to_expiry <= Decimal::ZERO
+        || volatility <= Decimal::ZERO
+        || spot <= Decimal::ZERO
+        || strike <= Decimal::ZERO
+    {
+        let intrinsic = match kind {
+            Kind::Call => spot.checked_sub(strike),
+            _ => strike.checked_sub(spot),
+        }
+        .ok_or_else(math_error!(MathUnderflow))?;
+        return Ok(intrinsic.max(Decimal::ZERO));
+    }
+
+    let volatility_sqrt_time = volatility
+        .checked_mul(
+            time_to_expiry
+                .sqrt()
+                .ok_or_else(math_error!(MathUnderflow))?,
+        )
+        .ok_or_else(math_error!(MathOverflow))?;
+    let half_variance = volatility
+        .checked_mul(volatility)
+        .ok_or_else(math_error!(MathOverflow))?
+        .checked_div(Decimal::from(2))
+        .ok_or_else(math_error!(DivisionByZero))?;
+    let drift = interest_rate
+        .checked_add(half_variance)
+        .ok_or_else(math_error!(MathOverflow))?
+        .checked_mul(time_to_expiry)
+        .ok_or_else(math_error!(MathOverflow))?;
+    let d1 = spot
+        .checked_div(strike)
+        .ok_or_else(math_error!(DivisionByZero))?
+        .ln()
+        .checked_add(drift)
+        .ok_or_else(math_error!(MathOverflow))?
+        .checked_div(volatility_sqrt_time)
+        .ok_or_else(math_error!(DivisionByZero))?;
+    let d2 = d1
+        .checked_sub(volatility_sqrt_time)
+        .ok_or_else(math_error!(MathUnderflow))?;
+    let discounted_strike = strike
+        .checked_div(get_growth_factor(interest_rate, time_to_expiry)?)
+        .ok_or_else(math_error!(DivisionByZero))?;
+
+    // `N(x) * a - N(y) * b`, both terms are non negative.
+    let weighted_difference = |a: Decimal, x: Decimal, b: Decimal, y: Decimal| {
+        a.checked_mul(x.norm_cdf())
+            .ok_or_else(math_error!(MathOverflow))?
+            .checked_sub(
+                b.checked_mul(y.norm_cdf())
+                    .ok_or_else(math_error!(MathOverflow))?,
+            )
+            .ok_or_else(math_error!(MathUnderflow))
+    };
+    let price = match kind {
+        Kind::Call => weighted_difference(spot, d1, discounted_strike, d2)?,
+        _ => weighted_difference(discounted_strike, -d2, spot, -d1)?,
+    };
+
+    Ok(price.max(Decimal::ZERO))
+}
+
+/// Reprices the mark of every live product at `spot`.
+///
+/// Options keep the volatility of their strike from the current surface in
+/// `Greeks` (sticky strike) and futures are priced at `spot * e^(rT)`.
+/// Options without a strike keep their current mark.
+pub fn get_theoretical_mark_prices(
+    zeta_group: &ZetaGroup,
+    greeks: &Greeks,
+    spot: NativePrice,
+    now_ts: u64,
+) -> Result<[u64; ACTIVE_MARKETS]> {
+    get_shocked_mark_prices(zeta_group, greeks, spot, Decimal::ZERO, now_ts)
+}
+
+/// Reprices Zeta's current marks from `current_spot` to `spot`.
+///
+/// Each mark in `Greeks` moves by the change in its theoretical price between
+/// the two spots, so at `current_spot` the marks are Zeta's own rather than
+/// the model's. Marks are floored at zero.
+pub fn get_repriced_mark_prices(
+    zeta_group: &ZetaGroup,
+    greeks: &Greeks,
+    current_spot: NativePrice,
+    spot: NativePrice,
+    now_ts: u64,
+) -> Result<[u64; ACTIVE_MARKETS]> {
+    shift_mark_prices(
+        greeks.mark_prices,
+        &get_theoretical_mark_prices(zeta_group, greeks, current_spot, now_ts)?,
+        &get_theoretical_mark_prices(zeta_group, greeks, spot, now_ts)?,
+    )
+}
+
+/// Moves every mark of `mark_prices` by `to - from`, floored at zero.
+pub fn shift_mark_prices(
+    mut mark_prices: [u64; ACTIVE_MARKETS],
+    from: &[u64; ACTIVE_MARKETS],
+    to: &[u64; ACTIVE_MARKETS],
+) -> Result<[u64; ACTIVE_MARKETS]> {
+    for (i, mark_price) in mark_prices.iter_mut().enumerate() {
+        let change = (to[i] as i128)
+            .checked_sub(from[i] as i128)
+            .ok_or_else(math_error!(MathUnderflow))?;
+        *mark_price = (*mark_price as i128)
+            .checked_add(change)
+            .ok_or_else(math_error!(MathOverflow))?
+            .max(0)
+            .try_into()
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))?;
+    }
+    Ok(mark_prices)
+}
+
+/// Same as `get_theoretical_mark_prices` with every option volatility
+/// shifted by `volatility_shock` (0.1 for +10 points), floored at zero.
+pub fn get_shocked_mark_prices(
+    zeta_group: &ZetaGroup,
+    greeks: &Greeks,
+    spot: NativePrice,
+    volatility_shock: Decimal,
+    now_ts: u64,
+) -> Result<[u64; ACTIVE_MARKETS]> {
+    let mut mark_prices: [u64; ACTIVE_MARKETS] = greeks.mark_prices;
+    let spot = spot.to_decimal();
+
+    for expiry_index in 0..ACTIVE_EXPIRIES {
+        let time_to_expiry =
+            get_time_to_expiry(zeta_group.expiry_series[expiry_index].expiry_ts, now_ts)?;
+        let interest_rate = get_interest_rate(greeks, expiry_index);
+        let product_greeks = greeks.get_product_greeks_slice(expiry_index);
+
+        for (product_index, product) in zeta_group
+            .get_products_slice(expiry_index)
+            .iter()
+            .enumerate()
+        {
+            let price = match product.kind {
+                Kind::Call | Kind::Put => {
+                    let strike = match product.strike.get_strike() {
+                        Ok(strike) => native_to_decimal(strike),
+                        Err(_) => continue,
+                    };
+                    let volatility =
+                        Decimal::from(product_greeks[product_index % NUM_STRIKES].volatility)
+                            .checked_add(volatility_shock)
+                            .ok_or_else(math_error!(MathOverflow))?
+                            .max(Decimal::ZERO);
+                    get_black_scholes_price(
+                        spot,
+                        strike,
+                        volatility,
+                        interest_rate,
+                        time_to_expiry,
+                        product.kind,
+                    )?
+                }
+                Kind::Future => get_future_price(spot, interest_rate, time_to_expiry)?,
+                Kind::Perp => spot,
+                Kind::Uninitialized => continue,
+            };
+
+            let market_index = get_products_slice_market_index(expiry_index, product_index);
+            mark_prices[market_index] = decimal_to_native(price)?;
+        }
+    }
+
+    Ok(mark_prices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(kind: Kind, spot: i64, time_to_expiry: Decimal) -> Decimal {
+        get_black_scholes_price(
+            Decimal::from(spot),
+            Decimal::from(100),
+            Decimal::new(2, 1),
+            Decimal::new(5, 2),
+            time_to_expiry,
+            kind,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_decimal_to_native() {
+        assert_eq!(
+            decimal_to_native(Decimal::new(12_3456786, 7)).unwrap(),
+            12_345_679
+        );
+        assert_eq!(decimal_to_native(Decimal::new(-5, 0)).unwrap(), 0);
+        assert_eq!(native_to_decimal(12_345_678), Decimal::new(12_345_678, 6));
+        assert!(decimal_to_native(Decimal::MAX).is_err());
+    }
+
+    #[test]
+    fn test_get_time_to_expiry() {
+        assert_eq!(
+            get_time_to_expiry(SECONDS_IN_A_YEAR / 2, 0).unwrap(),
+            Decimal::new(5, 1)
+        );
+        assert_eq!(get_time_to_expiry(100, 200).unwrap(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_get_black_scholes_price() {
+        let call = price(Kind::Call, 100, Decimal::ONE);
+        let put = price(Kind::Put, 100, Decimal::ONE);
+        assert_eq!(call.round_dp(2), Decimal::new(1045, 2));
+        assert_eq!(put.round_dp(2), Decimal::new(557, 2));
+
+        // Put-call parity, C - P = S - K * e^(-rT).
+        let forward_value = Decimal::from(100)
+            - Decimal::from(100) / get_growth_factor(Decimal::new(5, 2), Decimal::ONE).unwrap();
+        assert_eq!((call - put).round_dp(4), forward_value.round_dp(4));
+
+        // Intrinsic value at expiry.
+        assert_eq!(price(Kind::Call, 120, Decimal::ZERO), Decimal::from(20));
+        assert_eq!(price(Kind::Put, 120, Decimal::ZERO), Decimal::ZERO);
+
+        assert!(get_black_scholes_price(
+            Decimal::from(100),
+            Decimal::from(100),
+            Decimal::new(2, 1),
+            Decimal::ZERO,
+            Decimal::ONE,
+            Kind::Future,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_shift_mark_prices() {
+        let mut mark_prices = [0; ACTIVE_MARKETS];
+        let mut from = [0; ACTIVE_MARKETS];
+        let mut to = [0; ACTIVE_MARKETS];
+        mark_prices[..3].copy_from_slice(&[10_000_000, 5_000_000, 1_000_000]);
+        from[..3].copy_from_slice(&[9_000_000, 6_000_000, 3_000_000]);
+        to[..3].copy_from_slice(&[12_000_000, 4_000_000, 0]);
+
+        let shifted = shift_mark_prices(mark_prices, &from, &to).unwrap();
+        assert_eq!(shifted[..4], [13_000_000, 3_000_000, 0, 0]);
+    }
+
+    #[test]
+    fn test_get_future_price() {
+        assert_eq!(
+            get_future_price(Decimal::from(100), Decimal::new(5, 2), Decimal::ONE)
+                .unwrap()
+                .round_dp(4),
+            Decimal::new(1051271, 4)
+        );
+        assert!(get_future_price(Decimal::MAX, Decimal::new(5, 2), Decimal::ONE).is_err());
+    }
+}
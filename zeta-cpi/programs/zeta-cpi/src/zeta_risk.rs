@@ -0,0 +1,334 @@
+use crate::*;
+use std::convert::TryInto;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct Scenario {
+    // Relative spot move, NATIVE_PRECISION_DENOMINATOR = 100%.
+    pub spot_shock: i64,
+    // Absolute volatility move with PRICING_PRECISION, 100_000_000_000 = 10 points.
+    pub volatility_shock: i64,
+    // Time elapsed from now.
+    pub time_decay_seconds: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ScenarioResult {
+    pub scenario: Scenario,
+    pub spot_price: NativePrice,         // Shocked spot price.
+    pub account_equity: i64,             // Balance plus unrealized pnl at shocked marks.
+    pub pnl: i64,                        // Change in account equity from the unshocked scenario.
+    pub maintenance_margin: NativePrice, // Maintenance margin of positions at shocked spot and marks.
+    pub is_liquidatable: bool,           // Whether equity is below the maintenance margin.
+}
+
+/// Returns the shocked spot price, floored at one native unit as the
+/// margin calculations divide by spot.
+pub fn get_shocked_spot_price(spot_price: NativePrice, spot_shock: i64) -> Result<NativePrice> {
+    Ok(NativePrice(
+        (spot_price.get() as i128)
+            .checked_mul(
+                (NATIVE_PRECISION_DENOMINATOR as i128)
+                    .checked_add(spot_shock.into())
+                    .ok_or_else(math_error!(MathOverflow))?,
+            )
+            .ok_or_else(math_error!(MathOverflow))?
+            .checked_div(NATIVE_PRECISION_DENOMINATOR as i128)
+            .ok_or_else(math_error!(DivisionByZero))?
+            .max(1)
+            .try_into()
+            .map_err(|_| error!(FuzeErrorCode::MathOverflow))?,
+    ))
+}
+
+/// Revalues a margin account under a single scenario.
+///
+/// Zeta's marks are moved by their Black-Scholes change from the current spot
+/// to the shocked spot, off the current volatility surface shifted by the
+/// volatility shock, at `now_ts` plus the time decay. Maintenance margin uses
+/// the same per lot margin as Zeta, `get_maintenance_margin_per_lot`, at the
+/// shocked spot and marks. Pnl is measured from the account's equity at
+/// Zeta's marks, so the unshocked scenario matches `get_margin_account_state`.
+pub fn calculate_scenario(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+    scenario: Scenario,
+    now_ts: u64,
+) -> Result<ScenarioResult> {
+    let theoretical_mark_prices =
+        get_theoretical_mark_prices(zeta_group, greeks, spot_price, now_ts)?;
+    revalue_scenario(
+        zeta_group,
+        margin_account,
+        greeks,
+        &theoretical_mark_prices,
+        spot_price,
+        scenario,
+        now_ts,
+    )
+}
+
+/// Balance plus unrealized pnl of the positions at `mark_prices`.
+fn get_account_equity_for_marks(
+    margin_account: &MarginAccount,
+    mark_prices: &[u64],
+) -> Result<i64> {
+    to_signed(margin_account.balance)?
+        .checked_add(margin_account.get_unrealized_pnl_for_marks(mark_prices)?)
+        .ok_or_else(math_error!(MathOverflow))
+}
+
+fn revalue_scenario(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    theoretical_mark_prices: &[u64; ACTIVE_MARKETS],
+    spot_price: NativePrice,
+    scenario: Scenario,
+    now_ts: u64,
+) -> Result<ScenarioResult> {
+    let shocked_spot_price = get_shocked_spot_price(spot_price, scenario.spot_shock)?;
+    let volatility_shock =
+        Decimal::from_i128_with_scale(scenario.volatility_shock.into(), PRICING_PRECISION);
+    let mark_prices = shift_mark_prices(
+        greeks.mark_prices,
+        theoretical_mark_prices,
+        &get_shocked_mark_prices(
+            zeta_group,
+            greeks,
+            shocked_spot_price,
+            volatility_shock,
+            now_ts
+                .checked_add(scenario.time_decay_seconds)
+                .ok_or_else(math_error!(MathOverflow))?,
+        )?,
+    )?;
+
+    let maintenance_margin = margin_account.get_maintenance_margin_for_marks(
+        &mark_prices,
+        zeta_group,
+        shocked_spot_price,
+    )?;
+    let account_equity = get_account_equity_for_marks(margin_account, &mark_prices)?;
+    let baseline_equity = get_account_equity(margin_account, greeks)?;
+
+    Ok(ScenarioResult {
+        scenario,
+        spot_price: shocked_spot_price,
+        account_equity,
+        pnl: account_equity
+            .checked_sub(baseline_equity)
+            .ok_or_else(math_error!(MathUnderflow))?,
+        maintenance_margin,
+        is_liquidatable: account_equity < to_signed(maintenance_margin.get())?,
+    })
+}
+
+/// Revalues a margin account over every combination of spot shock,
+/// volatility shock and time decay.
+///
+/// Results are ordered by time decay, then volatility shock, then spot shock,
+/// so a spot x vol grid for a given day is a contiguous block.
+pub fn calculate_scenario_grid(
+    zeta_group: &ZetaGroup,
+    margin_account: &MarginAccount,
+    greeks: &Greeks,
+    spot_price: NativePrice,
+    spot_shocks: &[i64],
+    volatility_shocks: &[i64],
+    time_decays_seconds: &[u64],
+    now_ts: u64,
+) -> Result<Vec<ScenarioResult>> {
+    let theoretical_mark_prices =
+        get_theoretical_mark_prices(zeta_group, greeks, spot_price, now_ts)?;
+    let mut results =
+        Vec::with_capacity(spot_shocks.len() * volatility_shocks.len() * time_decays_seconds.len());
+
+    for &time_decay_seconds in time_decays_seconds {
+        for &volatility_shock in volatility_shocks {
+            for &spot_shock in spot_shocks {
+                let scenario = Scenario {
+                    spot_shock,
+                    volatility_shock,
+                    time_decay_seconds,
+                };
+                results.push(revalue_scenario(
+                    zeta_group,
+                    margin_account,
+                    greeks,
+                    &theoretical_mark_prices,
+                    spot_price,
+                    scenario,
+                    now_ts,
+                )?);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    const NOW_TS: u64 = 1_000_000;
+
+    // One lot long a future and one long a 100 strike call, both entered at
+    // their value at spot 100. Zeta marks both above the model.
+    fn long_account() -> (Box<ZetaGroup>, Box<Greeks>, Box<MarginAccount>) {
+        let mut zeta_group = Box::new(ZetaGroup::zeroed());
+        zeta_group.expiry_series[0].expiry_ts = NOW_TS + SECONDS_IN_A_YEAR;
+        for (i, kind) in [(0, Kind::Call), (NUM_STRIKES * 2, Kind::Future)] {
+            zeta_group.products[i].kind = kind;
+            zeta_group.products[i].strike = Strike {
+                is_set: true,
+                value: 100_000_000,
+            };
+        }
+
+        let mut greeks = Box::new(Greeks::zeroed());
+        greeks.product_greeks[0].volatility = Decimal::new(8, 1).into();
+        greeks.mark_prices[0] = 40_000_000;
+        greeks.mark_prices[NUM_STRIKES * 2] = 101_000_000;
+
+        let mut margin_account = Box::new(MarginAccount::zeroed());
+        margin_account.balance = 50_000_000;
+        margin_account.product_ledgers[0].position = Position {
+            size: 1_000,
+            cost_of_trades: 31_000_000,
+        };
+        margin_account.product_ledgers[NUM_STRIKES * 2].position = Position {
+            size: 1_000,
+            cost_of_trades: 100_000_000,
+        };
+        (zeta_group, greeks, margin_account)
+    }
+
+    fn scenario(spot_shock: i64, volatility_shock: i64, time_decay_seconds: u64) -> Scenario {
+        Scenario {
+            spot_shock,
+            volatility_shock,
+            time_decay_seconds,
+        }
+    }
+
+    #[test]
+    fn test_get_shocked_spot_price() {
+        assert_eq!(
+            get_shocked_spot_price(NativePrice(100_000_000), 10_000_000).unwrap(),
+            NativePrice(110_000_000)
+        );
+        assert_eq!(
+            get_shocked_spot_price(NativePrice(100_000_000), -25_000_000).unwrap(),
+            NativePrice(75_000_000)
+        );
+        assert_eq!(
+            get_shocked_spot_price(NativePrice(100_000_000), -100_000_000).unwrap(),
+            NativePrice(1)
+        );
+        assert!(get_shocked_spot_price(NativePrice(u64::MAX), 100_000_000).is_err());
+    }
+
+    #[test]
+    fn test_calculate_scenario() {
+        let (zeta_group, greeks, margin_account) = long_account();
+        let result = |scenario| {
+            calculate_scenario(
+                &zeta_group,
+                &margin_account,
+                &greeks,
+                NativePrice(100_000_000),
+                scenario,
+                NOW_TS,
+            )
+            .unwrap()
+        };
+
+        // The unshocked scenario is the account at Zeta's marks.
+        let unshocked = result(scenario(0, 0, 0));
+        let state = get_margin_account_state(
+            &zeta_group,
+            &margin_account,
+            &greeks,
+            NativePrice(100_000_000),
+            None,
+        )
+        .unwrap();
+        assert_eq!(unshocked.pnl, 0);
+        assert_eq!(unshocked.spot_price, NativePrice(100_000_000));
+        assert_eq!(unshocked.account_equity, state.account_equity);
+        assert_eq!(unshocked.maintenance_margin, state.maintenance_margin);
+        assert_eq!(
+            unshocked.is_liquidatable,
+            state.available_balance_maintenance < 0
+        );
+
+        // Long both, so pnl rises with spot and the call gains from volatility
+        // and loses to time.
+        let up = result(scenario(10_000_000, 0, 0));
+        assert_eq!(up.spot_price, NativePrice(110_000_000));
+        assert!(up.pnl > 10_000_000);
+        assert!(result(scenario(-10_000_000, 0, 0)).pnl < -10_000_000);
+        assert!(result(scenario(0, 100_000_000_000, 0)).pnl > 0);
+        assert!(result(scenario(0, 0, SECONDS_IN_A_YEAR / 12)).pnl < 0);
+        assert_eq!(up.account_equity, unshocked.account_equity + up.pnl);
+    }
+
+    #[test]
+    fn test_calculate_scenario_grid() {
+        let (zeta_group, greeks, margin_account) = long_account();
+        let results = calculate_scenario_grid(
+            &zeta_group,
+            &margin_account,
+            &greeks,
+            NativePrice(100_000_000),
+            &[-10_000_000, 0, 10_000_000],
+            &[0, 100_000_000_000],
+            &[0, 86_400],
+            NOW_TS,
+        )
+        .unwrap();
+
+        assert_eq!(results.len(), 12);
+        let order: Vec<(i64, i64, u64)> = results
+            .iter()
+            .map(|result| {
+                let scenario = result.scenario;
+                (
+                    scenario.spot_shock,
+                    scenario.volatility_shock,
+                    scenario.time_decay_seconds,
+                )
+            })
+            .collect();
+        assert_eq!(
+            order[..4],
+            [
+                (-10_000_000, 0, 0),
+                (0, 0, 0),
+                (10_000_000, 0, 0),
+                (-10_000_000, 100_000_000_000, 0)
+            ]
+        );
+        assert_eq!(order[6], (-10_000_000, 0, 86_400));
+
+        // Cells match the single scenario calculation.
+        assert_eq!(results[1].pnl, 0);
+        assert_eq!(
+            results[5].pnl,
+            calculate_scenario(
+                &zeta_group,
+                &margin_account,
+                &greeks,
+                NativePrice(100_000_000),
+                scenario(10_000_000, 100_000_000_000, 0),
+                NOW_TS,
+            )
+            .unwrap()
+            .pnl
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use crate::*;
 use std::cell::RefMut;
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 use std::ops::DerefMut;
 
 #[macro_export]
@@ -11,6 +11,14 @@ macro_rules! wrap_error {
     }};
 }
 
+/// Error closure for checked math, e.g. `.checked_add(x).ok_or_else(math_error!(MathOverflow))?`.
+#[macro_export]
+macro_rules! math_error {
+    ($err:ident) => {{
+        || error!(FuzeErrorCode::$err)
+    }};
+}
+
 pub fn deserialize_account_info_zerocopy<'a, T: bytemuck::Pod>(
     account_info: &'a AccountInfo,
 ) -> Result<RefMut<'a, T>> {
@@ -28,197 +36,263 @@ pub fn deserialize_account_info<'a, T: AccountSerialize + AccountDeserialize + O
     Ok(T::try_deserialize_unchecked(&mut data)?)
 }
 
-pub fn get_otm_amount(spot: u64, strike: u64, product: Kind) -> Result<u64> {
+pub fn get_otm_amount(
+    spot: NativePrice,
+    strike: NativePrice,
+    product: Kind,
+) -> Result<NativePrice> {
+    match product {
+        Kind::Call => Ok(strike.saturating_sub(spot)),
+        Kind::Put => Ok(spot.saturating_sub(strike)),
+        _ => return wrap_error!(Err(error!(FuzeErrorCode::UnsupportedKind))),
+    }
+}
+
+/// Converts a native amount to a signed amount for pnl and balance math.
+pub fn to_signed(amount: u64) -> Result<i64> {
+    i64::try_from(amount).map_err(|_| error!(FuzeErrorCode::MathOverflow))
+}
+
+/// Value of a single product at expiry for a given settlement spot price.
+pub fn get_intrinsic_value(
+    spot: NativePrice,
+    strike: NativePrice,
+    product: Kind,
+) -> Result<NativePrice> {
     match product {
-        Kind::Call => Ok((strike as i128)
-            .checked_sub(spot as i128)
-            .unwrap()
-            .max(0)
-            .try_into()
-            .unwrap()),
-        Kind::Put => Ok((spot as i128)
-            .checked_sub(strike as i128)
-            .unwrap()
-            .max(0)
-            .try_into()
-            .unwrap()),
+        Kind::Call => Ok(spot.saturating_sub(strike)),
+        Kind::Put => Ok(strike.saturating_sub(spot)),
+        Kind::Future | Kind::Perp => Ok(spot),
         _ => return wrap_error!(Err(error!(FuzeErrorCode::UnsupportedKind))),
     }
 }
 
 /// Initial margin for single product
 pub fn get_initial_margin_per_lot(
-    spot: u64,
-    strike: u64,
-    mark: u64,
+    spot: NativePrice,
+    strike: NativePrice,
+    mark: NativePrice,
     product: Kind,
     side: Side,
     margin_parameters: &MarginParameters,
-) -> Result<u64> {
-    let initial_margin: u128 = match product {
-        Kind::Future => (spot as u128)
-            .checked_mul(margin_parameters.future_margin_initial.into())
-            .unwrap()
-            .checked_div(NATIVE_PRECISION_DENOMINATOR)
-            .unwrap(),
+) -> Result<NativePrice> {
+    let initial_margin = match product {
+        Kind::Future => spot.checked_mul_pct(Pct(margin_parameters.future_margin_initial))?,
         Kind::Call | Kind::Put => match side {
-            Side::Bid => (spot as u128)
-                .checked_mul(margin_parameters.option_spot_percentage_long_initial.into())
-                .unwrap()
-                .checked_div(NATIVE_PRECISION_DENOMINATOR)
-                .unwrap()
-                .min(
-                    (mark as u128)
-                        .checked_mul(margin_parameters.option_mark_percentage_long_initial.into())
-                        .unwrap()
-                        .checked_div(NATIVE_PRECISION_DENOMINATOR)
-                        .unwrap(),
-                ),
+            Side::Bid => {
+                spot.checked_mul_pct(Pct(margin_parameters.option_spot_percentage_long_initial))?
+                    .min(mark.checked_mul_pct(Pct(
+                        margin_parameters.option_mark_percentage_long_initial,
+                    ))?)
+            }
             Side::Ask => {
-                let otm_amount: u128 = get_otm_amount(spot, strike, product)?.into();
-                let otm_pct = otm_amount
-                    .checked_mul(NATIVE_PRECISION_DENOMINATOR)
-                    .unwrap()
-                    .checked_div(spot.into())
-                    .unwrap();
-
-                let dynamic_margin_pct = (margin_parameters.option_dynamic_percentage_short_initial
-                    as u128)
-                    .checked_sub(otm_pct)
-                    .unwrap_or(0);
-
-                let margin_pct = dynamic_margin_pct.max(
-                    margin_parameters
-                        .option_spot_percentage_short_initial
-                        .into(),
-                );
-                margin_pct
-                    .checked_mul(spot.into())
-                    .unwrap()
-                    .checked_div(NATIVE_PRECISION_DENOMINATOR)
-                    .unwrap()
+                let otm_pct = Pct::from_ratio(get_otm_amount(spot, strike, product)?, spot)?;
+                let dynamic_margin_pct =
+                    Pct(margin_parameters.option_dynamic_percentage_short_initial)
+                        .saturating_sub(otm_pct);
+
+                let margin_pct = dynamic_margin_pct
+                    .max(Pct(margin_parameters.option_spot_percentage_short_initial));
+                spot.checked_mul_pct(margin_pct)?
             }
-            Side::Uninitialized => unreachable!(),
+            Side::Uninitialized => return wrap_error!(Err(error!(FuzeErrorCode::InvalidSide))),
         },
         _ => return wrap_error!(Err(error!(FuzeErrorCode::UnsupportedKind))),
     };
 
     if product == Kind::Put && side == Side::Ask {
-        let sell_put_cap_margin = (strike as u128)
-            .checked_mul(margin_parameters.option_short_put_cap_percentage as u128)
-            .unwrap()
-            .checked_div(NATIVE_PRECISION_DENOMINATOR)
-            .unwrap();
-
-        return Ok(u64::try_from(initial_margin.min(sell_put_cap_margin)).unwrap());
+        let sell_put_cap_margin =
+            strike.checked_mul_pct(Pct(margin_parameters.option_short_put_cap_percentage))?;
+        return Ok(initial_margin.min(sell_put_cap_margin));
     }
 
-    Ok(u64::try_from(initial_margin).unwrap())
+    Ok(initial_margin)
 }
 
 /// Maintenance margin for single product
 pub fn get_maintenance_margin_per_lot(
-    spot: u64,
-    strike: u64,
-    mark: u64,
+    spot: NativePrice,
+    strike: NativePrice,
+    mark: NativePrice,
     product: Kind,
     long: bool,
     margin_parameters: &MarginParameters,
-) -> Result<u64> {
-    let maintenance_margin: u128 = match product {
-        Kind::Future => (spot as u128)
-            .checked_mul(margin_parameters.future_margin_maintenance.into())
-            .unwrap()
-            .checked_div(NATIVE_PRECISION_DENOMINATOR)
-            .unwrap(),
+) -> Result<NativePrice> {
+    let maintenance_margin = match product {
+        Kind::Future => spot.checked_mul_pct(Pct(margin_parameters.future_margin_maintenance))?,
         Kind::Call | Kind::Put => {
             if long {
-                (spot as u128)
-                    .checked_mul(
-                        margin_parameters
-                            .option_spot_percentage_long_maintenance
-                            .into(),
-                    )
-                    .unwrap()
-                    .checked_div(NATIVE_PRECISION_DENOMINATOR)
-                    .unwrap()
-                    .min(
-                        (mark as u128)
-                            .checked_mul(
-                                margin_parameters
-                                    .option_mark_percentage_long_maintenance
-                                    .into(),
-                            )
-                            .unwrap()
-                            .checked_div(NATIVE_PRECISION_DENOMINATOR)
-                            .unwrap(),
-                    )
+                spot.checked_mul_pct(Pct(
+                    margin_parameters.option_spot_percentage_long_maintenance
+                ))?
+                .min(mark.checked_mul_pct(Pct(
+                    margin_parameters.option_mark_percentage_long_maintenance,
+                ))?)
             } else {
-                let otm_amount: u128 = get_otm_amount(spot, strike, product)?.into();
-                let otm_pct = otm_amount
-                    .checked_mul(NATIVE_PRECISION_DENOMINATOR)
-                    .unwrap()
-                    .checked_div(spot.into())
-                    .unwrap();
-
-                let dynamic_margin_pct: u128 =
-                    (margin_parameters.option_dynamic_percentage_short_maintenance as u128)
-                        .checked_sub(otm_pct)
-                        .unwrap_or(0);
-
-                let margin_pct = dynamic_margin_pct.max(
-                    margin_parameters
-                        .option_spot_percentage_short_maintenance
-                        .into(),
-                );
-                margin_pct
-                    .checked_mul(spot.into())
-                    .unwrap()
-                    .checked_div(NATIVE_PRECISION_DENOMINATOR)
-                    .unwrap()
+                let otm_pct = Pct::from_ratio(get_otm_amount(spot, strike, product)?, spot)?;
+                let dynamic_margin_pct =
+                    Pct(margin_parameters.option_dynamic_percentage_short_maintenance)
+                        .saturating_sub(otm_pct);
+
+                let margin_pct = dynamic_margin_pct.max(Pct(
+                    margin_parameters.option_spot_percentage_short_maintenance
+                ));
+                spot.checked_mul_pct(margin_pct)?
             }
         }
         _ => return wrap_error!(Err(error!(FuzeErrorCode::UnsupportedKind))),
     };
 
     if product == Kind::Put && !long {
-        let sell_put_cap_margin = (strike as u128)
-            .checked_mul(margin_parameters.option_short_put_cap_percentage as u128)
-            .unwrap()
-            .checked_div(NATIVE_PRECISION_DENOMINATOR)
-            .unwrap();
-
-        return Ok(u64::try_from(maintenance_margin.min(sell_put_cap_margin)).unwrap());
+        let sell_put_cap_margin =
+            strike.checked_mul_pct(Pct(margin_parameters.option_short_put_cap_percentage))?;
+        return Ok(maintenance_margin.min(sell_put_cap_margin));
     }
 
-    Ok(u64::try_from(maintenance_margin).unwrap())
+    Ok(maintenance_margin)
 }
 
-/// Returns the native oracle price (6.dp)
+/// Returns the native oracle price (6.dp), validated with the default
+/// `OracleConfig` at the current slot.
 ///
 /// # Arguments
 ///
-/// * `oracle` - Oracle account.
-pub fn get_native_oracle_price(oracle: &AccountInfo) -> u64 {
-    let oracle_price = pyth_client::Price::load(&oracle).unwrap();
-    (oracle_price.agg.price as u128)
-        .checked_mul(10u128.pow(PLATFORM_PRECISION.into()))
-        .unwrap()
-        .checked_div(10u128.pow((-oracle_price.expo).try_into().unwrap()))
-        .unwrap()
-        .try_into()
-        .unwrap()
+/// * `oracle` - Oracle source, see `load_oracle_source`.
+pub fn get_native_oracle_price(oracle: &dyn OracleSource) -> Result<NativePrice> {
+    get_oracle_price(oracle, PLATFORM_PRECISION).map(NativePrice)
 }
 
-pub fn get_oracle_price(oracle: &AccountInfo, precision: u32) -> i128 {
-    let oracle_price = pyth_client::Price::load(&oracle).unwrap();
-    (oracle_price.agg.price as u128)
-        .checked_mul(10u128.pow(precision))
-        .unwrap()
-        .checked_div(10u128.pow((-oracle_price.expo).try_into().unwrap()))
-        .unwrap()
-        .try_into()
-        .unwrap()
+pub fn get_oracle_price(oracle: &dyn OracleSource, precision: u32) -> Result<u64> {
+    Ok(oracle
+        .get_price(&OracleConfig::default(), Clock::get()?.slot, precision)?
+        .price)
+}
+
+/// Returns the native reference price (6.dp) of `reference`, validated with
+/// the default `OracleConfig` at the current slot.
+///
+/// # Arguments
+///
+/// * `reference` - The oracle itself for its own TWAP / EMA, or an `OracleEma`.
+pub fn get_native_reference_price(reference: &dyn OracleSource) -> Result<NativePrice> {
+    Ok(NativePrice(
+        reference
+            .get_reference_price(
+                &OracleConfig::default(),
+                Clock::get()?.slot,
+                PLATFORM_PRECISION,
+            )?
+            .price,
+    ))
+}
+
+/// Open orders accounts passed as remaining accounts have to be writable
+/// accounts of the DEX program, and at least one has to be given.
+pub fn validate_open_orders(open_orders: &[AccountInfo], dex_program: &Pubkey) -> Result<()> {
+    if open_orders.is_empty()
+        || open_orders
+            .iter()
+            .any(|account| !account.is_writable || account.owner != dex_program)
+    {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidOpenOrdersAccounts)));
+    }
+    Ok(())
+}
+
+/// An order's time in force has to be non zero and end by the expiry of the
+/// series it trades in, Zeta would otherwise leave it resting past expiry.
+pub fn validate_tif_offset(
+    zeta_group: &ZetaGroup,
+    market: &Pubkey,
+    tif_offset: Option<u16>,
+) -> Result<()> {
+    let tif_offset = match tif_offset {
+        Some(tif_offset) => tif_offset,
+        None => return Ok(()),
+    };
+    if tif_offset == 0 {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidTifOffset)));
+    }
+
+    let expiry_series = zeta_group.get_expiry_series_by_key(market)?;
+    let tif_end_ts = (Clock::get()?.unix_timestamp as u64)
+        .checked_add(u64::from(tif_offset))
+        .ok_or_else(math_error!(MathOverflow))?;
+    if tif_end_ts > expiry_series.expiry_ts {
+        return wrap_error!(Err(error!(FuzeErrorCode::TifExceedsExpiry)));
+    }
+    Ok(())
+}
+
+/// Number of orders `place_orders` attempts within `compute_unit_limit`, see
+/// `PLACE_ORDER_COMPUTE_UNITS`.
+pub fn get_max_batch_orders(compute_unit_limit: u32) -> usize {
+    (compute_unit_limit.saturating_sub(PLACE_ORDERS_BASE_COMPUTE_UNITS) / PLACE_ORDER_COMPUTE_UNITS)
+        as usize
+}
+
+/// Splits the remaining accounts of `place_orders` into its market groups,
+/// `PLACE_ORDERS_MARKET_ACCOUNTS` long with at least one given, and the
+/// optional trade ledger following them.
+pub fn split_place_orders_accounts<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<(&'a [AccountInfo<'info>], Option<&'a AccountInfo<'info>>)> {
+    let num_trade_ledgers = remaining_accounts.len() % PLACE_ORDERS_MARKET_ACCOUNTS;
+    if num_trade_ledgers > 1 {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidPlaceOrdersAccounts)));
+    }
+
+    let (market_groups, trade_ledger) =
+        remaining_accounts.split_at(remaining_accounts.len() - num_trade_ledgers);
+    if market_groups.is_empty() {
+        return wrap_error!(Err(error!(FuzeErrorCode::InvalidPlaceOrdersAccounts)));
+    }
+    Ok((market_groups, trade_ledger.first()))
+}
+
+/// Assembles the `PlaceOrder` accounts of an order on `market` from the shared
+/// accounts of `place_orders` and the market group trading `market`.
+pub fn get_batch_place_order_accounts<'info>(
+    accounts: &PlaceOrdersCaller<'info>,
+    market_groups: &[AccountInfo<'info>],
+    market: &Pubkey,
+) -> Result<PlaceOrder<'info>> {
+    let group = match market_groups
+        .chunks_exact(PLACE_ORDERS_MARKET_ACCOUNTS)
+        .find(|group| group[1].key == market)
+    {
+        Some(group) => group,
+        None => return wrap_error!(Err(error!(FuzeErrorCode::InvalidPlaceOrdersAccounts))),
+    };
+
+    Ok(PlaceOrder {
+        state: accounts.state.clone(),
+        zeta_group: accounts.zeta_group.clone(),
+        margin_account: accounts.margin_account.clone(),
+        authority: accounts.authority.clone(),
+        dex_program: accounts.dex_program.clone(),
+        token_program: accounts.token_program.clone(),
+        serum_authority: accounts.serum_authority.clone(),
+        greeks: accounts.greeks.clone(),
+        open_orders: group[0].clone(),
+        rent: accounts.rent.clone(),
+        market_accounts: MarketAccounts {
+            market: group[1].clone(),
+            request_queue: group[2].clone(),
+            event_queue: group[3].clone(),
+            bids: group[4].clone(),
+            asks: group[5].clone(),
+            order_payer_token_account: group[6].clone(),
+            coin_vault: group[7].clone(),
+            pc_vault: group[8].clone(),
+            coin_wallet: group[9].clone(),
+            pc_wallet: group[10].clone(),
+        },
+        oracle: accounts.oracle.clone(),
+        market_node: group[11].clone(),
+        market_mint: group[12].clone(),
+        mint_authority: accounts.mint_authority.clone(),
+    })
 }
 
 /// Returns the market index given an expiry index and index into the slice.
@@ -234,3 +308,19 @@ pub fn get_products_slice_market_index(expiry_index: usize, product_index: usize
         .checked_add(product_index)
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_max_batch_orders() {
+        assert_eq!(get_max_batch_orders(DEFAULT_COMPUTE_UNIT_LIMIT), 3);
+        assert_eq!(get_max_batch_orders(1_400_000), 27);
+        assert_eq!(
+            get_max_batch_orders(PLACE_ORDERS_BASE_COMPUTE_UNITS + PLACE_ORDER_COMPUTE_UNITS - 1),
+            0
+        );
+        assert_eq!(get_max_batch_orders(0), 0);
+    }
+}
@@ -50,6 +50,7 @@ describe("zeta-cpi", () => {
     userUsdc,
     openOrders,
     openOrdersMap,
+    tradeLedger: anchor.web3.PublicKey,
     market: Market,
     client: Client,
     side: types.Side;
@@ -251,6 +252,29 @@ describe("zeta-cpi", () => {
     console.log("Your transaction signature", tx);
   });
 
+  it("Init trade ledger", async () => {
+    [tradeLedger] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        Buffer.from("trade-ledger"),
+        client.getMarginAccountAddress(asset).toBuffer(),
+      ],
+      program.programId
+    );
+
+    const tx = await program.methods
+      .initializeTradeLedger()
+      .accounts({
+        tradeLedger,
+        zetaGroup: Exchange.getZetaGroupAddress(asset),
+        marginAccount: client.getMarginAccountAddress(asset),
+        authority: userKeypair.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc();
+
+    console.log("Your transaction signature", tx);
+  });
+
   it("Place order via CPI", async () => {
     const marketAccounts = {
       market: market.address,
@@ -301,6 +325,9 @@ describe("zeta-cpi", () => {
           mintAuthority: Exchange.mintAuthority,
         },
       })
+      .remainingAccounts([
+        { pubkey: tradeLedger, isSigner: false, isWritable: true },
+      ])
       .rpc();
 
     console.log("Your transaction signature", tx);
@@ -346,15 +373,18 @@ describe("zeta-cpi", () => {
       Exchange.getZetaGroupAddress(asset)
     );
 
-    let tx = await program.rpc.readProgramData({
-      accounts: {
-        state: Exchange.stateAddress,
-        zetaGroup: Exchange.getZetaGroupAddress(asset),
-        marginAccount: client.getMarginAccountAddress(asset),
-        greeks,
-        oracle: pythOracle,
-      },
-    });
+    let tx = await program.rpc.readProgramData(
+      { spot: {} },
+      {
+        accounts: {
+          state: Exchange.stateAddress,
+          zetaGroup: Exchange.getZetaGroupAddress(asset),
+          marginAccount: client.getMarginAccountAddress(asset),
+          greeks,
+          oracle: pythOracle,
+        },
+      }
+    );
     console.log("Your transaction signature", tx);
   });
 